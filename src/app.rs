@@ -4,20 +4,35 @@ use ratatui::backend::CrosstermBackend;
 use ratatui::layout::{Constraint, Direction, Layout, Rect};
 use ratatui::style::{Color, Modifier, Style};
 use ratatui::text::{Line, Span};
-use ratatui::widgets::{Block, Borders, List, ListItem, Paragraph, Wrap, Tabs};
+use ratatui::widgets::{Bar, BarChart, BarGroup, Block, Borders, Cell, List, ListItem, ListState, Paragraph, Row, Table, TableState, Wrap, Tabs};
 use ratatui::Terminal;
 use std::io::Stdout;
 use std::time::{Duration, Instant};
 
 use crate::db::Db;
-use crate::dice;
-use crate::models::{all_skills, Character};
+use crate::dice::{self, CritRule, DiceRoller, Natural};
+use crate::models::{ability_index, all_skills, class_save_proficiencies, Character, Coin, Condition, DamageType, DeathSave, Encumbrance, Item, Recharge, RollEffects, RollKind, RollRecord, Spell, Vitality, ABILITIES, EXHAUSTION_EFFECTS, parse_coins, parse_hit_dice, prepares_spells};
+use crate::stats::Distribution;
 
 pub enum Mode {
     List,
     Details,
     Edit,
     EditAddItem,
+    EditMacro,
+    EditSaves,
+    EditHp,
+    EditDefenses,
+    EditResource,
+    EditClass,
+    AddSpell,
+    CastSpell,
+    ConcentrationSave,
+    EditConditions,
+    AddItem,
+    EditItem,
+    SplitItem,
+    EditPurse,
     CreateName,
     CreateClass,
     CreateRace,
@@ -26,7 +41,12 @@ pub enum Mode {
     CreateAc,
     CreateSpeed,
     CreateSkills,
+    CreateSaves,
     Roll,
+    ShortRest,
+    LongRest,
+    Odds,
+    History,
 }
 
 pub struct App {
@@ -37,25 +57,61 @@ pub struct App {
     pub input: String,
     pub status: String,
     pub last_tick: Instant,
+    pub roller: DiceRoller,
+    pub crit_rule: CritRule,
+    /// Variant encumbrance: speed penalties at 5 × and 10 × Strength.
+    pub variant_encumbrance: bool,
+    /// Natural 20/1 of the last roll, highlighted while `status` still shows it.
+    last_natural: Option<(Natural, String)>,
     wizard: Option<NewCharDraft>,
+    odds: Option<Odds>,
+    history: Vec<RollRecord>,
+    history_selected: usize,
+    /// Only show rolls of the selected character in the history panel.
+    history_filter: bool,
     selected_spell_level: usize,
     detail_tab: usize,
+    /// Spellbook of the selected character, shown on the Spells tab.
+    spells: Vec<Spell>,
+    spell_selected: usize,
+    /// Items of the selected character, shown on the Inventory tab.
+    inventory: Vec<Item>,
+    item_selected: usize,
+    /// DC of a pending concentration save after taking damage.
+    concentration_dc: Option<i32>,
 }
 
 impl App {
-    pub fn new(db: Db) -> Result<Self> {
+    pub fn new(db: Db, roller: DiceRoller) -> Result<Self> {
         let items = db.list_characters()?;
+        let crit_rule = db.get_setting("crit_rule")?.map(|k| CritRule::from_key(&k)).unwrap_or_default();
+        let variant_encumbrance = db.get_setting("variant_encumbrance")?.is_some_and(|v| v == "on");
+        let mut status = String::from("🚪 q: quit • ✨ n: new • ✏️ e: edit • 🗑️ d: delete • 🎲 r: roll • ❤️ +/- hp • 🔮 [/] spend/regain slot • 🔢 1-9 select slot");
+        if let Some(seed) = roller.seed() { status.push_str(&format!(" • 🌱 seed {}", seed)); }
         Ok(Self {
             db,
             items,
             selected: 0,
             mode: Mode::List,
             input: String::new(),
-            status: String::from("🚪 q: quit • ✨ n: new • ✏️ e: edit • 🗑️ d: delete • 🎲 r: roll • ❤️ +/- hp • 🔮 [/] slot • 🔢 1-9 select slot"),
+            status,
             last_tick: Instant::now(),
+            roller,
+            crit_rule,
+            variant_encumbrance,
+            last_natural: None,
             wizard: None,
+            odds: None,
+            history: Vec::new(),
+            history_selected: 0,
+            history_filter: false,
             selected_spell_level: 1,
             detail_tab: 0,
+            spells: Vec::new(),
+            spell_selected: 0,
+            inventory: Vec::new(),
+            item_selected: 0,
+            concentration_dc: None,
         })
     }
 
@@ -96,13 +152,10 @@ impl App {
             let timeout = tick_rate
                 .checked_sub(self.last_tick.elapsed())
                 .unwrap_or(Duration::from_secs(0));
-            if crossterm::event::poll(timeout)? {
-                if let Event::Key(key) = event::read()? {
-                    if key.kind == KeyEventKind::Press {
-                        if self.on_key(key.code)? { break; }
-                    }
-                }
-            }
+            if crossterm::event::poll(timeout)?
+                && let Event::Key(key) = event::read()?
+                && key.kind == KeyEventKind::Press
+                && self.on_key(key.code)? { break; }
             if self.last_tick.elapsed() >= tick_rate {
                 self.last_tick = Instant::now();
             }
@@ -114,18 +167,19 @@ impl App {
         match self.mode {
             Mode::List => match code {
                 KeyCode::Char('q') => return Ok(true),
-                KeyCode::Down | KeyCode::Char('j') => {
-                    if !self.items.is_empty() {
-                        self.selected = (self.selected + 1).min(self.items.len().saturating_sub(1));
-                    }
+                KeyCode::Down | KeyCode::Char('j') if !self.items.is_empty() => {
+                    self.selected = (self.selected + 1).min(self.items.len().saturating_sub(1));
                 }
-                KeyCode::Up | KeyCode::Char('k') => {
-                    if !self.items.is_empty() {
-                        self.selected = self.selected.saturating_sub(1);
-                    }
+                KeyCode::Up | KeyCode::Char('k') if !self.items.is_empty() => {
+                    self.selected = self.selected.saturating_sub(1);
                 }
-                KeyCode::Enter => {
-                    if !self.items.is_empty() { self.mode = Mode::Details; self.status = details_status(); }
+                KeyCode::Enter if !self.items.is_empty() => {
+                    self.mode = Mode::Details;
+                    self.spell_selected = 0;
+                    self.item_selected = 0;
+                    let _ = self.reload_spells();
+                    let _ = self.reload_inventory();
+                    self.status = details_status();
                 }
                 KeyCode::Char('n') => {
                     self.mode = Mode::CreateName;
@@ -133,11 +187,11 @@ impl App {
                     self.status = String::from("✨ Create: Enter name. ⏎ Enter to confirm. ⎋ Esc to cancel.");
                     self.wizard = Some(NewCharDraft::default());
                 }
-                KeyCode::Char('e') => {
-                    if self.current_mut().is_some() {
-                        self.mode = Mode::Edit;
-                        self.status = String::from("✏️ Editing: ❤️ +/- hp • 🔮 [/] adjust • 🔢 1-9 select slot • 🎒 a/A add/remove item • 📈 l level up • 💾 s save • ⎋ Esc cancel");
-                    }
+                KeyCode::Char('e') if self.current_mut().is_some() => {
+                    self.mode = Mode::Edit;
+                    let _ = self.reload_spells();
+                    let _ = self.reload_inventory();
+                    self.status = String::from("✏️ Editing: ❤️ +/- hp • 🔮 [/] spend/regain • {/} slot max • 🔁 o auto slots • 🔢 1-9 select slot, 0 pact • 🎒 a/A add/remove item • ⚡ m macro • 🛡️ v saves • ⚔️ k class • ❤️ p hp amount • 🔥 R defenses • 🌀 n conditions • 💰 $ purse • 🔋 c resource • 📈 l level up • 🩹 x clear death saves • 💾 s save • ⎋ Esc cancel");
                 }
                 KeyCode::Char('d') => {
                     if let Some(charac) = self.items.get(self.selected).cloned() {
@@ -148,7 +202,21 @@ impl App {
                 KeyCode::Char('r') => {
                     self.mode = Mode::Roll;
                     self.input.clear();
-                    self.status = String::from("🎲 Type dice (e.g., d20, 2d6) then ⏎ Enter. ⎋ Esc cancel");
+                    self.status = String::from("🎲 Type dice (e.g., d20, 2d6+3), a macro, or 'death save' then ⏎ Enter. ⎋ Esc cancel");
+                }
+                KeyCode::Char('o') => { self.mode = Mode::Odds; self.input.clear(); self.odds = None; self.status = odds_status(); }
+                KeyCode::Char('H') => { self.mode = Mode::History; self.history_selected = 0; let _ = self.reload_history(); self.status = history_status(); }
+                KeyCode::Char('S') if !self.items.is_empty() => { self.mode = Mode::ShortRest; self.input.clear(); self.status = self.short_rest_status(); }
+                KeyCode::Char('L') if !self.items.is_empty() => { self.mode = Mode::LongRest; self.status = String::from("🛌 Long rest: ⏎ Enter to confirm. ⎋ Esc cancel"); }
+                KeyCode::Char('C') => {
+                    self.crit_rule = self.crit_rule.toggle();
+                    let _ = self.db.set_setting("crit_rule", self.crit_rule.key());
+                    self.status = format!("💥 Critical hits now use {}", self.crit_rule.label());
+                }
+                KeyCode::Char('V') => {
+                    self.variant_encumbrance = !self.variant_encumbrance;
+                    let _ = self.db.set_setting("variant_encumbrance", if self.variant_encumbrance { "on" } else { "off" });
+                    self.status = String::from(if self.variant_encumbrance { "⚖️ Variant encumbrance: speed -10 ft past 5 × STR, -20 ft past 10 × STR" } else { "⚖️ Standard encumbrance: only the carrying capacity (15 × STR) counts" });
                 }
                 _ => {}
             },
@@ -249,11 +317,22 @@ impl App {
                         .map(|s| s.trim().to_lowercase())
                         .filter(|s| !s.is_empty() && valids.contains(s))
                         .collect();
-                    if let Some(mut w) = self.wizard.take() {
+                    if let Some(w) = &mut self.wizard {
                         w.skill_proficiencies = picked;
+                        self.input = format_saves(&class_save_proficiencies(&w.class_name));
+                    }
+                    self.mode = Mode::CreateSaves;
+                    self.status = String::from("🛡️ Saving throw proficiencies (pre-filled from class), e.g., str, con");
+                }
+                KeyCode::Char(ch) => { self.input.push(ch); }
+                _ => {}
+            },
+            Mode::CreateSaves => match code {
+                KeyCode::Esc => { self.mode = Mode::List; self.status = default_status(); }
+                KeyCode::Enter => {
+                    if let Some(w) = self.wizard.take() {
                         let mut c = Character::default();
                         c.name = w.name;
-                        c.class_name = w.class_name;
                         c.race = w.race;
                         c.strength = w.abilities[0];
                         c.dexterity = w.abilities[1];
@@ -266,29 +345,100 @@ impl App {
                         c.armor_class = w.armor_class;
                         c.speed = w.speed;
                         c.skill_proficiencies = w.skill_proficiencies;
-                        let _ = self.db.insert_character(&mut c);
+                        c.save_proficiencies = parse_saves(&self.input);
+                        c.set_class(&w.class_name);
+                        if let Ok(id) = self.db.insert_character(&mut c)
+                            && let Ok(Some(created)) = self.db.get_character(id)
+                        {
+                            // Select the new character at its place in the name order.
+                            self.selected = self.items.partition_point(|other| other.name <= created.name);
+                            self.items.insert(self.selected, created);
+                        }
                     }
-                    let _ = self.reload();
                     self.mode = Mode::List; self.input.clear(); self.status = default_status();
                 }
-                KeyCode::Char(ch) => { self.input.push(ch); }
+                KeyCode::Char(ch) => self.input.push(ch),
+                KeyCode::Backspace => { self.input.pop(); },
                 _ => {}
             },
             Mode::Details => match code {
                 KeyCode::Esc => { self.mode = Mode::List; self.status = default_status(); }
                 KeyCode::Left | KeyCode::Char('h') => { self.detail_tab = self.detail_tab.saturating_sub(1); }
-                KeyCode::Right | KeyCode::Char('l') => { self.detail_tab = (self.detail_tab + 1).min(2); }
-                KeyCode::Char('e') => { if self.current_mut().is_some() { self.mode = Mode::Edit; self.status = edit_status(); } }
-                KeyCode::Char('r') => { self.mode = Mode::Roll; self.input.clear(); self.status = String::from("🎲 Type: NdM, skill, or ability. ⎋ Esc cancel"); }
+                KeyCode::Right | KeyCode::Char('l') => { self.detail_tab = (self.detail_tab + 1).min(3); }
+                KeyCode::Down | KeyCode::Char('j') if self.detail_tab == 3 => { self.spell_selected = (self.spell_selected + 1).min(self.spells.len().saturating_sub(1)); }
+                KeyCode::Up | KeyCode::Char('k') if self.detail_tab == 3 => { self.spell_selected = self.spell_selected.saturating_sub(1); }
+                KeyCode::Char('a') if self.detail_tab == 3 => { self.mode = Mode::AddSpell; self.input.clear(); self.status = String::from("✨ Type name | level | class | school | casting time | range | components | duration | flags (c, r) | description, then ⏎ Enter. ⎋ Esc cancel"); }
+                KeyCode::Down | KeyCode::Char('j') if self.detail_tab == 2 => { self.item_selected = (self.item_selected + 1).min(self.inventory.len().saturating_sub(1)); }
+                KeyCode::Up | KeyCode::Char('k') if self.detail_tab == 2 => { self.item_selected = self.item_selected.saturating_sub(1); }
+                KeyCode::Char('J') if self.detail_tab == 2 => self.move_item(1),
+                KeyCode::Char('K') if self.detail_tab == 2 => self.move_item(-1),
+                KeyCode::Char('a') if self.detail_tab == 2 => { self.mode = Mode::AddItem; self.input.clear(); self.status = add_item_status(); }
+                KeyCode::Enter if self.detail_tab == 2 => {
+                    if let Some(it) = self.inventory.get(self.item_selected) {
+                        self.input = format_item(it);
+                        self.mode = Mode::EditItem;
+                        self.status = String::from("🎒 Edit name | quantity | weight | value | flags (e, a) | tags | description, then ⏎ Enter. ⎋ Esc cancel");
+                    }
+                }
+                KeyCode::Char('x') if self.detail_tab == 2 => {
+                    if let Some(it) = self.inventory.get(self.item_selected) {
+                        self.status = format!("🗑️ {} removed from the inventory", it.name);
+                        if let Some(id) = it.id { let _ = self.db.delete_item(id); }
+                    }
+                    let _ = self.reload_inventory();
+                }
+                KeyCode::Char('+') if self.detail_tab == 2 => self.adjust_item_quantity(1),
+                KeyCode::Char('-') if self.detail_tab == 2 => self.adjust_item_quantity(-1),
+                KeyCode::Char('s') if self.detail_tab == 2 => match self.inventory.get(self.item_selected) {
+                    Some(it) if it.quantity > 1 => {
+                        self.input = (it.quantity / 2).to_string();
+                        self.mode = Mode::SplitItem;
+                        self.status = format!("✂️ How many of the {} {} go into a new stack? ⏎ Enter. ⎋ Esc cancel", it.quantity, it.name);
+                    }
+                    Some(it) => self.status = format!("⚠️ Only one {} to split", it.name),
+                    None => {}
+                },
+                KeyCode::Char('x') if self.detail_tab == 3 => {
+                    if let Some(id) = self.spells.get(self.spell_selected).and_then(|s| s.id) { let _ = self.db.delete_spell(id); }
+                    let _ = self.reload_spells();
+                }
+                KeyCode::Char('p') if self.detail_tab == 3 => { self.status = self.toggle_prepared(); }
+                KeyCode::Char('z') => {
+                    if let Some(c) = self.current_mut() && let Some(spell) = c.concentration.take() {
+                        self.status = format!("💨 {} stops concentrating on {}", c.name, spell);
+                        let _ = self.save_current();
+                    }
+                }
+                KeyCode::Char('c') if self.detail_tab == 3 => match self.spells.get(self.spell_selected) {
+                    Some(s) if s.is_cantrip() => { self.status = self.cast_spell("").unwrap_or_else(|e| format!("⚠️ {}", e)); }
+                    Some(s) => {
+                        self.input = s.level.to_string();
+                        self.mode = Mode::CastSpell;
+                        self.status = String::from("🔮 Type a slot level (higher to upcast), p for a pact slot or r to cast as a ritual, then ⏎ Enter. ⎋ Esc cancel");
+                    }
+                    None => {}
+                },
+                KeyCode::Char('e') if self.current_mut().is_some() => { self.mode = Mode::Edit; self.status = edit_status(); }
+                KeyCode::Char('r') => { self.mode = Mode::Roll; self.input.clear(); self.status = String::from("🎲 Type: dice, macro, skill, ability, save like 'dex save', 'spell attack' or 'death save' (add 'attack' after a d20 attack roll, adv/dis for advantage). ⎋ Esc cancel"); }
+                KeyCode::Char('o') => { self.mode = Mode::Odds; self.input.clear(); self.odds = None; self.status = odds_status(); }
+                KeyCode::Char('H') => { self.mode = Mode::History; self.history_selected = 0; let _ = self.reload_history(); self.status = history_status(); }
+                KeyCode::Char('S') if !self.items.is_empty() => { self.mode = Mode::ShortRest; self.input.clear(); self.status = self.short_rest_status(); }
+                KeyCode::Char('L') if !self.items.is_empty() => { self.mode = Mode::LongRest; self.status = String::from("🛌 Long rest: ⏎ Enter to confirm. ⎋ Esc cancel"); }
                 _ => {}
             },
             Mode::Edit => match code {
                 KeyCode::Esc => { self.mode = Mode::List; self.status = default_status(); }
-                KeyCode::Char('+') => { if let Some(c) = self.current_mut(){ c.change_hp(1); let _ = self.save_current(); } }
-                KeyCode::Char('-') => { if let Some(c) = self.current_mut(){ c.change_hp(-1); let _ = self.save_current(); } }
+                KeyCode::Char('+') => { if let Some(c) = self.current_mut(){ c.heal(1); let _ = self.save_current(); } }
+                KeyCode::Char('-') => {
+                    if let Some(c) = self.current_mut(){ c.take_damage(1, false); let _ = self.save_current(); }
+                    if let Some(prompt) = self.check_concentration(1) { self.mode = Mode::ConcentrationSave; self.status = prompt; }
+                }
+                KeyCode::Char('p') => { self.mode = Mode::EditHp; self.status = String::from("❤️ Type -14 or 12 fire damage (crit at the end for a critical hit), +7 heal, =20 set HP, t5 temp HP or r3 max reduction, then ⏎ Enter. ⎋ Esc cancel"); self.input.clear(); }
                 KeyCode::Char('l') => { if let Some(c) = self.current_mut(){ c.level_up(); let _ = self.save_current(); } }
+                KeyCode::Char('x') => { if let Some(c) = self.current_mut(){ c.reset_death_saves(); let _ = self.save_current(); } }
                 KeyCode::Left | KeyCode::Char('h') => { self.detail_tab = self.detail_tab.saturating_sub(1); }
-                KeyCode::Right => { self.detail_tab = (self.detail_tab + 1).min(2); }
+                KeyCode::Right => { self.detail_tab = (self.detail_tab + 1).min(3); }
+                KeyCode::Char('0') => { self.selected_spell_level = 0; self.status = String::from("✏️ Editing: 🔮 Pact slots selected"); }
                 KeyCode::Char('1') => { self.selected_spell_level = 1; self.status = format!("✏️ Editing: 🔮 Slot L{} selected", self.selected_spell_level); }
                 KeyCode::Char('2') => { self.selected_spell_level = 2; self.status = format!("✏️ Editing: 🔮 Slot L{} selected", self.selected_spell_level); }
                 KeyCode::Char('3') => { self.selected_spell_level = 3; self.status = format!("✏️ Editing: 🔮 Slot L{} selected", self.selected_spell_level); }
@@ -308,18 +458,267 @@ impl App {
                     if let Some(c) = self.current_mut(){ c.adjust_spell_slot(lvl, 1); }
                     let _ = self.save_current();
                 }
-                KeyCode::Char('a') => { self.mode = Mode::EditAddItem; self.status = String::from("🎒 Type item then ⏎ Enter to add. ⎋ Esc cancel"); self.input.clear(); }
-                KeyCode::Char('A') => { if let Some(c) = self.current_mut(){ if !c.inventory.is_empty(){ c.remove_item(c.inventory.len()-1); let _ = self.save_current(); } } }
+                KeyCode::Char('{') => {
+                    let lvl = self.selected_spell_level;
+                    if lvl > 0 && let Some(c) = self.current_mut() { c.spell_slots_manual = true; c.adjust_spell_slot_max(lvl, -1); }
+                    let _ = self.save_current();
+                }
+                KeyCode::Char('}') => {
+                    let lvl = self.selected_spell_level;
+                    if lvl > 0 && let Some(c) = self.current_mut() { c.spell_slots_manual = true; c.adjust_spell_slot_max(lvl, 1); }
+                    let _ = self.save_current();
+                }
+                KeyCode::Char('a') => { self.mode = Mode::EditAddItem; self.status = add_item_status(); self.input.clear(); }
+                KeyCode::Char('A') => {
+                    if let Some(id) = self.inventory.last().and_then(|it| it.id) { let _ = self.db.delete_item(id); }
+                    let _ = self.reload_inventory();
+                }
+                KeyCode::Char('v') => {
+                    if let Some(c) = self.items.get(self.selected) { self.input = format_saves(&c.save_proficiencies); }
+                    self.mode = Mode::EditSaves;
+                    self.status = String::from("🛡️ Edit saving throw proficiencies (e.g., str, con) then ⏎ Enter. ⎋ Esc cancel");
+                }
+                KeyCode::Char('m') => { self.mode = Mode::EditMacro; self.status = String::from("⚡ Type name = expression, tagged attack or check for conditions (e.g., longsword-hit = 1d20+@str+@prof attack), or -name to delete, then ⏎ Enter. ⎋ Esc cancel"); self.input.clear(); }
+                KeyCode::Char('R') => {
+                    if let Some(c) = self.items.get(self.selected) { self.input = format_defenses(c); }
+                    self.mode = Mode::EditDefenses;
+                    self.status = String::from("🔥 Edit damage defenses (e.g., resist fire, cold; immune poison; vuln radiant) then ⏎ Enter. ⎋ Esc cancel");
+                }
+                KeyCode::Char('o') => {
+                    if let Some(c) = self.current_mut() {
+                        c.spell_slots_manual = !c.spell_slots_manual;
+                        c.sync_spell_slots();
+                        self.status = String::from(if c.spell_slots_manual { "🔮 Spell slots set by hand ({/} change the max)" } else { "🔮 Spell slots follow class and level" });
+                    }
+                    let _ = self.save_current();
+                }
+                KeyCode::Char('k') => {
+                    if let Some(c) = self.items.get(self.selected) { self.input = c.class_name.clone(); }
+                    self.mode = Mode::EditClass;
+                    self.status = String::from("⚔️ Type a class (e.g., Wizard) or multiclass (e.g., Paladin 2 / Sorcerer 3) then ⏎ Enter. ⎋ Esc cancel");
+                }
+                KeyCode::Char('n') => {
+                    if let Some(c) = self.items.get(self.selected) { self.input = format_conditions(c); }
+                    self.mode = Mode::EditConditions;
+                    self.status = String::from("🌀 Edit conditions (e.g., poisoned, prone, exhaustion 2) then ⏎ Enter. ⎋ Esc cancel");
+                }
+                KeyCode::Char('$') => { self.mode = Mode::EditPurse; self.input.clear(); self.status = String::from("💰 Type +3 gp 5 sp to add, -12 gp to spend (change is made automatically) or 30 sp to gp to convert, then ⏎ Enter. ⎋ Esc cancel"); }
+                KeyCode::Char('c') => { self.mode = Mode::EditResource; self.status = String::from("🔋 Type name = max short|long (e.g., rage = 3 long), name -1 / name +1 to spend/regain, or -name to delete. ⎋ Esc cancel"); self.input.clear(); }
                 KeyCode::Char('s') => { let _ = self.save_current(); self.status = String::from("💾 Saved."); }
                 _ => {}
             },
-            Mode::EditAddItem => match code {
-                KeyCode::Esc => { self.mode = Mode::Edit; self.status = edit_status(); }
+            Mode::AddSpell => match code {
+                KeyCode::Esc => { self.mode = Mode::Details; self.input.clear(); self.status = details_status(); }
+                KeyCode::Enter => match Spell::parse(&self.input) {
+                    Ok(mut spell) => {
+                        spell.character_id = self.items.get(self.selected).and_then(|c| c.id);
+                        let _ = self.db.insert_spell(&mut spell);
+                        let _ = self.reload_spells();
+                        self.spell_selected = self.spells.iter().position(|s| s.id == spell.id).unwrap_or(0);
+                        self.status = format!("✨ {} added to the spellbook", spell.name);
+                        self.input.clear();
+                        self.mode = Mode::Details;
+                    }
+                    Err(e) => self.status = format!("⚠️ {}", e),
+                },
+                KeyCode::Char(ch) => self.input.push(ch),
+                KeyCode::Backspace => { self.input.pop(); },
+                _ => {}
+            },
+            Mode::CastSpell => match code {
+                KeyCode::Esc => { self.mode = Mode::Details; self.input.clear(); self.status = details_status(); }
+                KeyCode::Enter => {
+                    let inp = self.input.trim().to_lowercase();
+                    match self.cast_spell(&inp) {
+                        Ok(msg) => { self.status = msg; self.input.clear(); self.mode = Mode::Details; }
+                        Err(e) => self.status = format!("⚠️ {}", e),
+                    }
+                }
+                KeyCode::Char(ch) => self.input.push(ch),
+                KeyCode::Backspace => { self.input.pop(); },
+                _ => {}
+            },
+            Mode::EditClass => match code {
+                KeyCode::Esc => { self.mode = Mode::Edit; self.input.clear(); self.status = edit_status(); }
+                KeyCode::Enter => {
+                    let class = self.input.trim().to_string();
+                    if class.is_empty() {
+                        self.status = String::from("⚠️ Class can't be empty");
+                    } else {
+                        if let Some(c) = self.current_mut() { c.set_class(&class); }
+                        let _ = self.save_current();
+                        self.input.clear();
+                        self.mode = Mode::Edit;
+                        self.status = edit_status();
+                    }
+                }
+                KeyCode::Char(ch) => self.input.push(ch),
+                KeyCode::Backspace => { self.input.pop(); },
+                _ => {}
+            },
+            Mode::EditResource => match code {
+                KeyCode::Esc => { self.mode = Mode::Edit; self.input.clear(); self.status = edit_status(); }
+                KeyCode::Enter => {
+                    let inp = self.input.trim().to_lowercase();
+                    match self.apply_resource_input(&inp) {
+                        Ok(msg) => { self.input.clear(); self.mode = Mode::Edit; self.status = msg; let _ = self.save_current(); }
+                        Err(e) => { self.status = format!("⚠️ {}", e); }
+                    }
+                }
+                KeyCode::Char(ch) => self.input.push(ch),
+                KeyCode::Backspace => { self.input.pop(); },
+                _ => {}
+            },
+            Mode::EditPurse => match code {
+                KeyCode::Esc => { self.mode = Mode::Edit; self.input.clear(); self.status = edit_status(); }
+                KeyCode::Enter => {
+                    let inp = self.input.trim().to_lowercase();
+                    match self.apply_purse_input(&inp) {
+                        Ok(msg) => { self.input.clear(); self.mode = Mode::Edit; self.status = msg; let _ = self.save_current(); }
+                        Err(e) => { self.status = format!("⚠️ {}", e); }
+                    }
+                }
+                KeyCode::Char(ch) => self.input.push(ch),
+                KeyCode::Backspace => { self.input.pop(); },
+                _ => {}
+            },
+            Mode::EditAddItem | Mode::AddItem => match code {
+                KeyCode::Esc => {
+                    self.input.clear();
+                    if matches!(self.mode, Mode::EditAddItem) { self.mode = Mode::Edit; self.status = edit_status(); } else { self.mode = Mode::Details; self.status = details_status(); }
+                }
+                KeyCode::Enter => match Item::parse(&self.input) {
+                    Ok(mut item) => {
+                        item.character_id = self.items.get(self.selected).and_then(|c| c.id);
+                        let _ = self.db.insert_item(&mut item);
+                        let _ = self.reload_inventory();
+                        self.item_selected = self.inventory.iter().position(|it| it.id == item.id).unwrap_or(0);
+                        self.status = format!("🎒 {} × {} added to the inventory", item.quantity, item.name);
+                        self.input.clear();
+                        self.mode = if matches!(self.mode, Mode::EditAddItem) { Mode::Edit } else { Mode::Details };
+                    }
+                    Err(e) => self.status = format!("⚠️ {}", e),
+                },
+                KeyCode::Char(ch) => self.input.push(ch),
+                KeyCode::Backspace => { self.input.pop(); },
+                _ => {}
+            },
+            Mode::EditItem => match code {
+                KeyCode::Esc => { self.mode = Mode::Details; self.input.clear(); self.status = details_status(); }
+                KeyCode::Enter => match Item::parse(&self.input) {
+                    Ok(edited) => {
+                        if let Some(it) = self.inventory.get_mut(self.item_selected) {
+                            *it = Item { id: it.id, character_id: it.character_id, ..edited };
+                            let _ = self.db.update_item(it);
+                            self.status = format!("🎒 {} updated", it.name);
+                        }
+                        self.input.clear();
+                        self.mode = Mode::Details;
+                    }
+                    Err(e) => self.status = format!("⚠️ {}", e),
+                },
+                KeyCode::Char(ch) => self.input.push(ch),
+                KeyCode::Backspace => { self.input.pop(); },
+                _ => {}
+            },
+            Mode::SplitItem => match code {
+                KeyCode::Esc => { self.mode = Mode::Details; self.input.clear(); self.status = details_status(); }
                 KeyCode::Enter => {
-                    let item = self.input.trim().to_string();
+                    let inp = self.input.trim().to_string();
+                    match self.split_item(&inp) {
+                        Ok(msg) => { self.status = msg; self.input.clear(); self.mode = Mode::Details; }
+                        Err(e) => self.status = format!("⚠️ {}", e),
+                    }
+                }
+                KeyCode::Char(ch) => self.input.push(ch),
+                KeyCode::Backspace => { self.input.pop(); },
+                _ => {}
+            },
+            Mode::EditSaves => match code {
+                KeyCode::Esc => { self.mode = Mode::Edit; self.input.clear(); self.status = edit_status(); }
+                KeyCode::Enter => {
+                    let saves = parse_saves(&self.input);
+                    if let Some(c) = self.current_mut() { c.save_proficiencies = saves; }
+                    let _ = self.save_current();
                     self.input.clear();
-                    if let Some(c) = self.current_mut(){ c.add_item(item); let _ = self.save_current(); }
                     self.mode = Mode::Edit;
+                    self.status = edit_status();
+                }
+                KeyCode::Char(ch) => self.input.push(ch),
+                KeyCode::Backspace => { self.input.pop(); },
+                _ => {}
+            },
+            Mode::EditDefenses => match code {
+                KeyCode::Esc => { self.mode = Mode::Edit; self.input.clear(); self.status = edit_status(); }
+                KeyCode::Enter => {
+                    let input = self.input.clone();
+                    match self.current_mut().map_or(Ok(()), |c| set_defenses(c, &input)) {
+                        Ok(()) => { let _ = self.save_current(); self.input.clear(); self.mode = Mode::Edit; self.status = edit_status(); }
+                        Err(e) => self.status = format!("⚠️ {}", e),
+                    }
+                }
+                KeyCode::Char(ch) => self.input.push(ch),
+                KeyCode::Backspace => { self.input.pop(); },
+                _ => {}
+            },
+            Mode::EditConditions => match code {
+                KeyCode::Esc => { self.mode = Mode::Edit; self.input.clear(); self.status = edit_status(); }
+                KeyCode::Enter => {
+                    let input = self.input.clone();
+                    match self.current_mut().map_or(Ok(()), |c| set_conditions(c, &input)) {
+                        Ok(()) => { let _ = self.save_current(); self.input.clear(); self.mode = Mode::Edit; self.status = edit_status(); }
+                        Err(e) => self.status = format!("⚠️ {}", e),
+                    }
+                }
+                KeyCode::Char(ch) => self.input.push(ch),
+                KeyCode::Backspace => { self.input.pop(); },
+                _ => {}
+            },
+            Mode::EditHp => match code {
+                KeyCode::Esc => { self.mode = Mode::Edit; self.input.clear(); self.status = edit_status(); }
+                KeyCode::Enter => {
+                    let inp = self.input.trim().to_lowercase();
+                    match self.apply_hp_input(&inp) {
+                        Ok(msg) => {
+                            self.input.clear();
+                            self.mode = if self.concentration_dc.is_some() { Mode::ConcentrationSave } else { Mode::Edit };
+                            self.status = msg;
+                            let _ = self.save_current();
+                        }
+                        Err(e) => { self.status = format!("⚠️ {}", e); }
+                    }
+                }
+                KeyCode::Char(ch) => self.input.push(ch),
+                KeyCode::Backspace => { self.input.pop(); },
+                _ => {}
+            },
+            Mode::ConcentrationSave => match code {
+                // The save can't be skipped: Esc rolls it without advantage.
+                KeyCode::Esc => { self.status = self.roll_concentration(dice::RollMode::Normal); self.mode = Mode::Edit; self.input.clear(); }
+                KeyCode::Enter => {
+                    let adv = match self.input.trim().to_lowercase().as_str() {
+                        "" => Some(dice::RollMode::Normal),
+                        "adv" | "advantage" => Some(dice::RollMode::Advantage),
+                        "dis" | "disadvantage" => Some(dice::RollMode::Disadvantage),
+                        _ => None,
+                    };
+                    match adv {
+                        Some(adv) => { self.status = self.roll_concentration(adv); self.mode = Mode::Edit; self.input.clear(); }
+                        None => self.status = String::from("⚠️ Leave empty or type adv/dis"),
+                    }
+                }
+                KeyCode::Char(ch) => self.input.push(ch),
+                KeyCode::Backspace => { self.input.pop(); },
+                _ => {}
+            },
+            Mode::EditMacro => match code {
+                KeyCode::Esc => { self.mode = Mode::Edit; self.status = edit_status(); }
+                KeyCode::Enter => {
+                    let inp = self.input.trim().to_string();
+                    match self.apply_macro_input(&inp) {
+                        Ok(msg) => { self.input.clear(); self.mode = Mode::Edit; self.status = msg; let _ = self.save_current(); }
+                        Err(e) => { self.status = format!("⚠️ {}", e); }
+                    }
                 }
                 KeyCode::Char(ch) => self.input.push(ch),
                 KeyCode::Backspace => { self.input.pop(); },
@@ -329,31 +728,13 @@ impl App {
                 KeyCode::Esc => { self.mode = Mode::List; self.input.clear(); self.status = default_status(); }
                 KeyCode::Enter => {
                     let inp = self.input.trim().to_lowercase();
-                    let name = self.items.get(self.selected).map(|c| c.name.clone()).unwrap_or_default();
-                    let mut desc = inp.clone();
-                    let (total, rolls);
-                    if let Some(c) = self.items.get(self.selected) {
-                        if dice::parse_dice(&inp).is_some() {
-                            let (t, r) = dice::roll(&inp, 0);
-                            total = t; rolls = r;
-                        } else if all_skills().iter().any(|(s, _)| *s == inp) {
-                            let modi = c.skill_modifier(&inp);
-                            let (t, r) = dice::roll("1d20", modi);
-                            total = t; rolls = r; desc = format!("{} check", capitalize(&inp));
-                        } else {
-                            // ability?
-                            let abi = ["str","dex","con","int","wis","cha"];
-                            if abi.contains(&inp.as_str()) {
-                                let modi = c.ability_modifier_by_name(&inp);
-                                let (t, r) = dice::roll("1d20", modi);
-                                total = t; rolls = r; desc = format!("{} ability", inp.to_uppercase());
-                            } else {
-                                let (t, r) = dice::roll("1d20", 0);
-                                total = t; rolls = r; desc = "d20".into();
-                            }
+                    match self.roll_prompt(&inp) {
+                        Ok((status, natural)) => {
+                            self.last_natural = natural.map(|n| (n, status.clone()));
+                            self.status = status;
                         }
-                    } else { let (t, r) = dice::roll("1d20", 0); total = t; rolls = r; }
-                    self.status = format!("🧙 {} rolls {}: {:?} total {}", name, desc, rolls, total);
+                        Err(e) => self.status = format!("⚠️ Can't roll '{}': {}", inp, e),
+                    }
                     self.mode = Mode::List;
                     self.input.clear();
                 }
@@ -361,10 +742,501 @@ impl App {
                 KeyCode::Backspace => { self.input.pop(); },
                 _ => {}
             },
+            Mode::ShortRest => match code {
+                KeyCode::Esc => { self.mode = Mode::List; self.input.clear(); self.status = default_status(); }
+                KeyCode::Enter => {
+                    let inp = self.input.trim().to_lowercase();
+                    let sizes: Vec<i32> = self.items.get(self.selected).map(|c| c.hit_dice().iter().map(|h| h.size).collect()).unwrap_or_default();
+                    let spend = parse_hit_dice(&inp);
+                    let missing = spend.iter().flatten().find_map(|(_, s)| s.filter(|s| !sizes.contains(s)));
+                    match (spend, missing) {
+                        (Some(_), Some(size)) => self.status = format!("⚠️ No d{} hit dice to spend", size),
+                        (Some(spend), None) => {
+                            if let Some(c) = self.current_mut() { c.short_rest(); }
+                            self.status = self.spend_hit_dice(&spend);
+                            self.mode = Mode::List;
+                            self.input.clear();
+                        }
+                        (None, _) => self.status = format!("⚠️ '{}' isn't a number of hit dice (e.g. 2 or 1d10 2d6)", inp),
+                    }
+                }
+                KeyCode::Char(ch) => self.input.push(ch),
+                KeyCode::Backspace => { self.input.pop(); },
+                _ => {}
+            },
+            Mode::LongRest => match code {
+                KeyCode::Esc => { self.mode = Mode::List; self.status = default_status(); }
+                KeyCode::Enter => {
+                    self.status = match self.current_mut() {
+                        Some(c) if c.can_long_rest() => { c.long_rest(); format!("🛌 {} finishes a long rest → {} (hit dice {})", c.name, hp_text(c), hit_dice_text(c)) }
+                        Some(c) => format!("⚠️ {} needs at least 1 HP to benefit from a long rest", c.name),
+                        None => default_status(),
+                    };
+                    let _ = self.save_current();
+                    self.mode = Mode::List;
+                }
+                _ => {}
+            },
+            Mode::Odds => match code {
+                KeyCode::Esc => { self.mode = Mode::List; self.input.clear(); self.odds = None; self.status = default_status(); }
+                KeyCode::Enter => {
+                    let inp = self.input.trim().to_lowercase();
+                    match self.analyze_input(&inp) {
+                        Ok(odds) => { self.status = odds_status(); self.odds = Some(odds); }
+                        Err(e) => { self.status = format!("⚠️ Can't analyze '{}': {}", inp, e); self.odds = None; }
+                    }
+                }
+                KeyCode::Char(ch) => self.input.push(ch),
+                KeyCode::Backspace => { self.input.pop(); },
+                _ => {}
+            },
+            Mode::History => match code {
+                KeyCode::Esc => { self.mode = Mode::List; self.status = default_status(); }
+                KeyCode::Down | KeyCode::Char('j') => { self.history_selected = (self.history_selected + 1).min(self.history.len().saturating_sub(1)); }
+                KeyCode::Up | KeyCode::Char('k') => { self.history_selected = self.history_selected.saturating_sub(1); }
+                KeyCode::Char('f') => { self.history_filter = !self.history_filter; self.history_selected = 0; let _ = self.reload_history(); }
+                _ => {}
+            },
         }
         Ok(false)
     }
 
+    fn reload_history(&mut self) -> Result<()> {
+        let character_id = if self.history_filter { self.items.get(self.selected).and_then(|c| c.id) } else { None };
+        self.history = self.db.list_rolls(character_id)?;
+        Ok(())
+    }
+
+    /// Adds (`name = expression`) or deletes (`-name`) a macro of the current character.
+    fn apply_macro_input(&mut self, inp: &str) -> Result<String, String> {
+        let Some(c) = self.current_mut() else { return Err(String::from("No character selected")) };
+        if let Some(name) = inp.strip_prefix('-') {
+            let name = name.trim();
+            return if c.remove_macro(name) { Ok(format!("🗑️ Macro '{}' deleted", name)) } else { Err(format!("No macro named '{}'", name)) };
+        }
+        let Some((name, expr)) = inp.split_once('=') else { return Err(String::from("Use name = expression or name = attack ; damage")) };
+        let name = name.trim();
+        if name.is_empty() || name.contains(char::is_whitespace) {
+            return Err(String::from("Macro names can't be empty or contain spaces"));
+        }
+        let (expr, kind) = RollKind::split_tag(expr);
+        for part in expr.split(';') {
+            c.parse_roll(part).map_err(|e| format!("Invalid expression: {}", e))?;
+        }
+        c.set_macro(name, expr, kind);
+        Ok(format!("⚡ Macro '{}' saved", name.to_lowercase()))
+    }
+
+    fn reload_spells(&mut self) -> Result<()> {
+        self.spells = match self.items.get(self.selected).and_then(|c| c.id) {
+            Some(id) => self.db.list_spells(id)?,
+            None => Vec::new(),
+        };
+        self.spell_selected = self.spell_selected.min(self.spells.len().saturating_sub(1));
+        Ok(())
+    }
+
+    fn reload_inventory(&mut self) -> Result<()> {
+        self.inventory = match self.items.get(self.selected).and_then(|c| c.id) {
+            Some(id) => self.db.list_items(id)?,
+            None => Vec::new(),
+        };
+        self.item_selected = self.item_selected.min(self.inventory.len().saturating_sub(1));
+        Ok(())
+    }
+
+    /// Moves the selected item `delta` places up (negative) or down the inventory.
+    fn move_item(&mut self, delta: isize) {
+        let from = self.item_selected;
+        let Some(to) = from.checked_add_signed(delta).filter(|to| *to < self.inventory.len()) else { return };
+        self.inventory.swap(from, to);
+        self.item_selected = to;
+        let ids: Vec<i64> = self.inventory.iter().filter_map(|it| it.id).collect();
+        let _ = self.db.reorder_items(&ids);
+    }
+
+    /// Adds to or takes from the selected stack, keeping at least one (x deletes).
+    fn adjust_item_quantity(&mut self, delta: i32) {
+        let Some(it) = self.inventory.get_mut(self.item_selected) else { return };
+        it.quantity = (it.quantity + delta).max(1);
+        let _ = self.db.update_item(it);
+        self.status = format!("🎒 {} × {}", it.quantity, it.name);
+    }
+
+    /// Moves `input` of the selected stack into a new stack right below it.
+    fn split_item(&mut self, input: &str) -> Result<String, String> {
+        let Some(it) = self.inventory.get_mut(self.item_selected) else { return Err(String::from("No item selected")) };
+        let count = input.parse::<i32>().ok().filter(|n| (1..it.quantity).contains(n))
+            .ok_or_else(|| format!("Split off 1 to {}", it.quantity - 1))?;
+        it.quantity -= count;
+        let _ = self.db.update_item(it);
+        let mut stack = Item { id: None, quantity: count, ..it.clone() };
+        let status = format!("✂️ {} split into {} and {}", it.name, it.quantity, count);
+        let _ = self.db.insert_item(&mut stack);
+        self.item_selected += 1;
+        self.inventory.insert(self.item_selected, stack);
+        let ids: Vec<i64> = self.inventory.iter().filter_map(|it| it.id).collect();
+        let _ = self.db.reorder_items(&ids);
+        Ok(status)
+    }
+
+    /// Prepares or unprepares the selected spell, within the prepared-spell limit.
+    fn toggle_prepared(&mut self) -> String {
+        let Some(c) = self.items.get(self.selected) else { return String::from("⚠️ No character selected") };
+        let prepared = self.spells.iter().filter(|s| s.prepared && !s.is_cantrip()).count() as i32;
+        let limit = c.prepared_limit();
+        let Some(spell) = self.spells.get_mut(self.spell_selected) else { return String::from("⚠️ No spell selected") };
+        if spell.is_cantrip() {
+            return String::from("✨ Cantrips are always ready");
+        }
+        if !spell.class.is_empty() && !prepares_spells(&spell.class) {
+            return format!("✨ {} spells are known, not prepared", spell.class);
+        }
+        if !spell.prepared && limit.is_some_and(|l| prepared >= l) {
+            return format!("⚠️ Already {} of {} spells prepared", prepared, limit.unwrap_or_default());
+        }
+        spell.prepared = !spell.prepared;
+        let _ = self.db.update_spell(spell);
+        format!("📖 {} {}", spell.name, if spell.prepared { "prepared" } else { "no longer prepared" })
+    }
+
+    /// Casts the selected spell: `3` spends a level 3 slot (upcasting when higher
+    /// than the spell), `p` a pact slot and `r` casts a ritual without a slot.
+    fn cast_spell(&mut self, inp: &str) -> Result<String, String> {
+        let spell = self.spells.get(self.spell_selected).cloned().ok_or_else(|| String::from("No spell selected"))?;
+        let Some(c) = self.current_mut() else { return Err(String::from("No character selected")) };
+        let ritual = matches!(inp, "r" | "ritual");
+        if ritual && !spell.ritual && !spell.is_cantrip() {
+            return Err(format!("{} isn't a ritual", spell.name));
+        }
+        // Rituals can be cast from the spellbook without preparing them.
+        if !ritual && !spell.prepared && c.must_prepare(&spell) {
+            return Err(format!("{} isn't prepared", spell.name));
+        }
+        let slot = match inp {
+            _ if spell.is_cantrip() || ritual => None,
+            "p" | "pact" => {
+                if c.pact_slots == 0 || c.pact_slot_level < spell.level { return Err(String::from("No usable pact slot left")); }
+                c.adjust_spell_slot(0, -1);
+                Some(c.pact_slot_level)
+            }
+            level => {
+                let level = level.parse::<i32>().ok().filter(|l| (spell.level..=9).contains(l))
+                    .ok_or_else(|| format!("Slot level must be from {} to 9", spell.level))?;
+                if c.spell_slots[level as usize - 1] > 0 {
+                    c.adjust_spell_slot(level as usize, -1);
+                } else if c.pact_slot_level == level && c.pact_slots > 0 {
+                    c.adjust_spell_slot(0, -1);
+                } else {
+                    return Err(format!("No level {} slots left", level));
+                }
+                Some(level)
+            }
+        };
+        let how = match slot {
+            None if spell.is_cantrip() => String::from("(cantrip)"),
+            None => String::from("as a ritual"),
+            Some(l) if l > spell.level => format!("with a level {} slot (upcast from {})", l, spell.level),
+            Some(l) => format!("with a level {} slot", l),
+        };
+        let mut status = format!("🔮 {} casts {} {}", c.name, spell.name, how);
+        if spell.concentration && let Some(old) = c.concentration.replace(spell.name.clone()) && old != spell.name {
+            status.push_str(&format!(" • ⚠️ stops concentrating on {}", old));
+        }
+        let character_id = c.id;
+        let _ = self.db.insert_roll(&mut RollRecord::cast(character_id, &spell, &status));
+        let _ = self.save_current();
+        Ok(status)
+    }
+
+    /// After the current character takes `damage`, queues a concentration save and
+    /// returns its prompt if they're still concentrating.
+    fn check_concentration(&mut self, damage: i32) -> Option<String> {
+        let spell = self.items.get(self.selected)?.concentration.clone().filter(|_| damage > 0)?;
+        let dc = Character::concentration_dc(damage);
+        self.concentration_dc = Some(dc);
+        self.input.clear();
+        Some(format!("🧠 Concentration on {}: CON save DC {} — ⏎ Enter to roll (type adv/dis first if needed), ⎋ Esc to roll it plain", spell, dc))
+    }
+
+    /// Rolls the pending concentration save, ending concentration on a failure.
+    fn roll_concentration(&mut self, adv: dice::RollMode) -> String {
+        let Some(dc) = self.concentration_dc.take() else { return edit_status() };
+        let Some(c) = self.items.get(self.selected) else { return String::from("⚠️ No character selected") };
+        let fx = c.roll_effects(RollKind::Save(Some(2)));
+        let adv = fx.mode(adv);
+        let res = self.roller.roll_expr(&dice::d20_check(c.save_modifier("con"), adv));
+        self.log_roll(&format!("CON save (concentration){}{}", adv.label(), fx.note()), &res);
+        let Some(c) = self.current_mut() else { return String::new() };
+        let spell = c.concentration.clone().unwrap_or_default();
+        let status = if res.total >= dc {
+            format!("🧠 {} keeps concentrating on {}: {} vs DC {}", c.name, spell, res, dc)
+        } else {
+            c.concentration = None;
+            format!("💨 {} loses concentration on {}: {} vs DC {}", c.name, spell, res, dc)
+        };
+        let _ = self.save_current();
+        status
+    }
+
+    /// Defines (`rage = 3 long`), spends or regains (`rage -1`, `ki +2`) or deletes
+    /// (`-rage`) a resource of the current character.
+    fn apply_resource_input(&mut self, inp: &str) -> Result<String, String> {
+        let Some(c) = self.current_mut() else { return Err(String::from("No character selected")) };
+        if let Some(name) = inp.strip_prefix('-') {
+            let name = name.trim();
+            return if c.remove_resource(name) { Ok(format!("🗑️ Resource '{}' deleted", name)) } else { Err(format!("No resource named '{}'", name)) };
+        }
+        if let Some((name, rest)) = inp.split_once('=') {
+            let name = name.trim();
+            if name.is_empty() || name.contains(char::is_whitespace) {
+                return Err(String::from("Resource names can't be empty or contain spaces"));
+            }
+            let (max, recharge) = rest.trim().split_once(char::is_whitespace).unwrap_or((rest.trim(), "long"));
+            let max: i32 = max.parse().ok().filter(|n| *n > 0).ok_or_else(|| format!("'{}' isn't a valid number of uses", max))?;
+            let recharge = match recharge.trim() {
+                "short" => Recharge::Short,
+                "long" => Recharge::Long,
+                other => return Err(format!("Recharge must be short or long, not '{}'", other)),
+            };
+            c.set_resource(name, max, recharge);
+            return Ok(format!("🔋 Resource '{}' saved: {} per {}", name, max, recharge.label()));
+        }
+        let Some((name, delta)) = inp.rsplit_once(char::is_whitespace) else { return Err(String::from("Use name = max short|long, name -1, name +1 or -name")) };
+        let delta: i32 = delta.trim_start_matches('+').parse().map_err(|_| format!("'{}' isn't an amount like -1 or +1", delta))?;
+        let r = c.find_resource_mut(name.trim()).ok_or_else(|| format!("No resource named '{}'", name.trim()))?;
+        r.current = (r.current + delta).clamp(0, r.max);
+        Ok(format!("🔋 {} {}/{}", capitalize(&r.name), r.current, r.max))
+    }
+
+    /// Adds (`+3 gp 5 sp`), spends (`-12 gp`) or converts (`30 sp to gp`) coins
+    /// in the current character's purse.
+    fn apply_purse_input(&mut self, inp: &str) -> Result<String, String> {
+        let Some(c) = self.current_mut() else { return Err(String::from("No character selected")) };
+        let mut purse = c.purse;
+        let action = if let Some(coins) = inp.strip_prefix('+') {
+            for (amount, coin) in parse_coins(coins)? { purse.add(amount, coin)?; }
+            format!("gains {}", coins.trim())
+        } else if let Some(coins) = inp.strip_prefix('-') {
+            for (amount, coin) in parse_coins(coins)? { purse.spend(amount, coin)?; }
+            format!("spends {}", coins.trim())
+        } else if let Some((coins, to)) = inp.trim_start_matches("convert").split_once(" to ") {
+            let to = Coin::from_name(to).ok_or_else(|| format!("'{}' isn't a coin (cp, sp, ep, gp, pp)", to.trim()))?;
+            let [(amount, from)] = parse_coins(coins)?[..] else { return Err(String::from("Convert one kind of coin at a time, e.g. 30 sp to gp")) };
+            let got = purse.convert(amount, from, to)?;
+            format!("exchanges {} {} for {} {}", got * to.value() / from.value(), from.name(), got, to.name())
+        } else {
+            return Err(String::from("Use +3 gp 5 sp, -12 gp or 30 sp to gp"));
+        };
+        c.purse = purse;
+        Ok(format!("💰 {} {} → {} (= {} gp)", c.name, action, c.purse, c.purse.total_gp()))
+    }
+
+    /// Applies an HP amount to the current character: `-14` or `12 fire` damage
+    /// (`crit` at the end for a critical hit), `+7` healing, `=20` current HP, `t5` temporary HP or `r3` HP maximum reduction.
+    fn apply_hp_input(&mut self, inp: &str) -> Result<String, String> {
+        let inp = if inp.starts_with(|ch: char| ch.is_ascii_digit()) { format!("-{}", inp) } else { inp.to_string() };
+        let (inp, critical) = match inp.strip_suffix("crit") {
+            Some(rest) if inp.starts_with('-') => (rest.trim_end().to_string(), true),
+            _ => (inp, false),
+        };
+        let mut chars = inp.chars();
+        let kind = chars.next().ok_or_else(|| String::from("Enter an amount like -14, 12 fire, +7, =20, t5 or r3"))?;
+        let (amount, damage_type) = match chars.as_str().trim().split_once(char::is_whitespace) {
+            Some((amount, t)) if kind == '-' => (amount, Some(DamageType::from_name(t).ok_or_else(|| format!("Unknown damage type '{}'", t.trim()))?)),
+            _ => (chars.as_str().trim(), None),
+        };
+        let amount: i32 = amount.parse().ok().filter(|n| *n >= 0).ok_or_else(|| format!("'{}' isn't a valid amount", amount))?;
+        let Some(c) = self.current_mut() else { return Err(String::from("No character selected")) };
+        match kind {
+            '-' => {
+                let damage = c.resolve_damage(amount, damage_type);
+                let absorbed = c.take_damage(damage.total, critical);
+                let temp = if absorbed > 0 { format!(" ({} absorbed by temp HP)", absorbed) } else { String::new() };
+                let crit = if critical { " from a critical hit" } else { "" };
+                let mut status = format!("💥 {} takes {} damage{}{} → {}", c.name, damage, crit, temp, hp_text(c));
+                let character_id = c.id;
+                let _ = self.db.insert_roll(&mut RollRecord::damage(character_id, &damage));
+                if let Some(prompt) = self.check_concentration(damage.total) { status = format!("{} • {}", status, prompt); }
+                Ok(status)
+            }
+            '+' => { c.heal(amount); Ok(format!("💚 {} heals {} → {}", c.name, amount, hp_text(c))) }
+            '=' => { c.set_hp(amount); Ok(format!("❤️ {} is at {}", c.name, hp_text(c))) }
+            't' => { c.gain_temp_hp(amount); Ok(format!("🔰 {} has {} temp HP", c.name, c.hp_temp)) }
+            'r' => { c.set_hp_max_reduction(amount); Ok(format!("🩸 {} HP max reduced by {} → {}", c.name, c.hp_max_reduction, hp_text(c))) }
+            _ => Err(String::from("Start with - (damage), + (heal), = (set HP), t (temp HP) or r (max reduction)")),
+        }
+    }
+
+    /// Resolves roll prompt input to a label and expression: a macro, a skill, an
+    /// ability, a saving throw (`dex save`) or a dice expression, optionally
+    /// followed by `adv`/`dis`. Conditions apply to any roll of a plain d20: attack
+    /// macros and rolls ending in `attack` as attacks, everything else as a check.
+    fn resolve_roll(&self, inp: &str) -> Result<(String, dice::Expr), dice::DiceError> {
+        let (check, adv) = dice::split_advantage(inp);
+        if let Some(c) = self.items.get(self.selected) {
+            let d20_effects = |expr: &dice::Expr, kind: Option<RollKind>| match kind {
+                Some(kind) if expr.has_d20() => c.roll_effects(kind),
+                _ => RollEffects::default(),
+            };
+            if let Some(m) = c.find_macro(check) {
+                let expr = c.parse_roll(m.attack())?;
+                let fx = d20_effects(&expr, m.roll_kind());
+                let adv = fx.mode(adv);
+                return Ok((format!("{}{}{}", m.name, adv.label(), fx.note()), expr.with_mode(adv)));
+            }
+            if all_skills().iter().any(|(s, _)| *s == check) {
+                let fx = c.roll_effects(RollKind::Check);
+                let adv = fx.mode(adv);
+                return Ok((format!("{} check{}{}", capitalize(check), adv.label(), fx.note()), dice::d20_check(c.skill_modifier(check), adv)));
+            }
+            if ABILITIES.contains(&check) {
+                let fx = c.roll_effects(RollKind::Check);
+                let adv = fx.mode(adv);
+                return Ok((format!("{} ability{}{}", check.to_uppercase(), adv.label(), fx.note()), dice::d20_check(c.ability_modifier_by_name(check), adv)));
+            }
+            if check == "spell attack" {
+                let bonus = c.reference("spell").ok_or_else(|| dice::DiceError::UnknownReference(String::from("spell")))?;
+                let fx = c.roll_effects(RollKind::Attack);
+                let adv = fx.mode(adv);
+                return Ok((format!("Spell attack{}{}", adv.label(), fx.note()), dice::d20_check(bonus, adv)));
+            }
+            if let Some(i) = check.strip_suffix(" save").and_then(ability_index) {
+                let ability = ABILITIES[i];
+                let fx = c.roll_effects(RollKind::Save(Some(i)));
+                let adv = fx.mode(adv);
+                return Ok((format!("{} save{}{}", ability.to_uppercase(), adv.label(), fx.note()), dice::d20_check(c.save_modifier(ability), adv)));
+            }
+            let (roll, kind) = RollKind::split_tag(check);
+            let expr = c.parse_roll(roll)?;
+            let fx = d20_effects(&expr, kind);
+            let adv = fx.mode(adv);
+            return Ok((format!("{}{}{}", check, adv.label(), fx.note()), expr.with_mode(adv)));
+        }
+        Ok((inp.to_string(), dice::parse(check)?.with_mode(adv)))
+    }
+
+    /// Rolls roll prompt input, logs it to the history and returns the status line
+    /// together with any natural 20/1 it should be highlighted for.
+    fn roll_prompt(&mut self, inp: &str) -> Result<(String, Option<Natural>), dice::DiceError> {
+        let name = self.items.get(self.selected).map(|c| c.name.clone()).unwrap_or_default();
+        let (check, adv) = dice::split_advantage(inp);
+        if check == "death save" {
+            return Ok(self.roll_death_save(adv));
+        }
+        if let Some(c) = self.items.get(self.selected)
+            && let Some(i) = check.strip_suffix(" save").and_then(ability_index)
+            && c.find_macro(check).is_none()
+        {
+            let fx = c.roll_effects(RollKind::Save(Some(i)));
+            if !fx.auto_fail.is_empty() {
+                return Ok((format!("❌ {} automatically fails the {} save ({})", name, ABILITIES[i].to_uppercase(), fx.auto_fail.join(", ")), None));
+            }
+        }
+        let attack = self.items.get(self.selected).and_then(|c| c.find_macro(check)).filter(|m| m.damage().is_some()).cloned();
+        let Some(m) = attack else {
+            let (label, expr) = self.resolve_roll(inp)?;
+            let res = self.roller.roll_expr(&expr);
+            self.log_roll(&label, &res);
+            let natural = res.natural();
+            return Ok((format!("🧙 {} rolls {}: {}{}", name, label, res, natural.map_or("", Natural::tag)), natural));
+        };
+
+        // Attack macro: roll to hit, then damage unless it's a natural 1.
+        let c = &self.items[self.selected];
+        let fx = c.roll_effects(RollKind::Attack);
+        let adv = fx.mode(adv);
+        let hit_expr = c.parse_roll(m.attack())?.with_mode(adv);
+        let dmg_expr = c.parse_roll(m.damage().unwrap_or_default())?;
+        let hit = self.roller.roll_expr(&hit_expr);
+        self.log_roll(&format!("{} attack{}{}", m.name, adv.label(), fx.note()), &hit);
+        let natural = hit.natural();
+        let mut status = format!("⚔️ {} attacks with {}: {}{}", name, m.name, hit, natural.map_or("", Natural::tag));
+        if natural == Some(Natural::Fumble) {
+            status.push_str(" • no damage");
+        } else {
+            let (label, dmg_expr) = if natural == Some(Natural::Crit) {
+                (format!("{} crit damage ({})", m.name, self.crit_rule.label()), dmg_expr.critical(self.crit_rule))
+            } else {
+                (format!("{} damage", m.name), dmg_expr)
+            };
+            let dmg = self.roller.roll_expr(&dmg_expr);
+            self.log_roll(&label, &dmg);
+            status.push_str(&format!(" • 🗡️ damage {}", dmg));
+        }
+        Ok((status, natural))
+    }
+
+    fn roll_death_save(&mut self, adv: dice::RollMode) -> (String, Option<Natural>) {
+        let Some(c) = self.items.get(self.selected) else { return (String::from("⚠️ No character selected"), None) };
+        if c.vitality() != Vitality::Dying {
+            return (format!("⚠️ {} isn't dying", c.name), None);
+        }
+        let fx = c.roll_effects(RollKind::Save(None));
+        let adv = fx.mode(adv);
+        let res = self.roller.roll_expr(&dice::d20_check(0, adv));
+        self.log_roll(&format!("Death save{}{}", adv.label(), fx.note()), &res);
+        let natural = res.natural();
+        let Some(c) = self.current_mut() else { return (String::new(), None) };
+        let outcome = match c.apply_death_save(res.total, natural) {
+            Some(DeathSave::Success) => format!("success ({}/3)", c.death_successes),
+            Some(DeathSave::Failure) => format!("failure ({}/3)", c.death_failures),
+            Some(DeathSave::Revived) => String::from("back up with 1 HP!"),
+            Some(DeathSave::Stabilized) => String::from("stable"),
+            Some(DeathSave::Died) => String::from("dead"),
+            None => String::new(),
+        };
+        let status = format!("💀 {} rolls a death save: {}{} → {}", c.name, res, natural.map_or("", Natural::tag), outcome);
+        let _ = self.save_current();
+        (status, natural)
+    }
+
+    fn short_rest_status(&self) -> String {
+        let Some(c) = self.items.get(self.selected) else { return default_status() };
+        format!("🏕️ Short rest: type hit dice to spend (e.g. 2 or 1d10 2d6, {:+} CON each, blank for none) then ⏎ Enter. ⎋ Esc cancel", c.con_mod())
+    }
+
+    /// Spends hit dice of the current character as `(count, size)` pairs from
+    /// [`parse_hit_dice`], healing by each roll plus CON.
+    fn spend_hit_dice(&mut self, spend: &[(i32, Option<i32>)]) -> String {
+        let Some(c) = self.current_mut() else { return String::from("⚠️ No character selected") };
+        let spent: Vec<i32> = spend.iter().flat_map(|&(count, size)| c.spend_hit_dice(count, size)).collect();
+        let exprs: Vec<dice::Expr> = spent.iter().map(|&size| c.hit_die_roll(size)).collect();
+        let mut rolls = Vec::new();
+        let mut healed = 0;
+        for expr in &exprs {
+            let res = self.roller.roll_expr(expr);
+            self.log_roll("Hit die", &res);
+            healed += res.total.max(0);
+            rolls.push(res.to_string());
+        }
+        let Some(c) = self.current_mut() else { return String::new() };
+        c.heal(healed);
+        let status = if spent.is_empty() {
+            format!("🏕️ {} rests without spending hit dice ({} left)", c.name, hit_dice_text(c))
+        } else {
+            format!("🏕️ {} spends {} hit dice: {} → +{} HP → {} ({} left)", c.name, spent.len(), rolls.join(", "), healed, hp_text(c), hit_dice_text(c))
+        };
+        let _ = self.save_current();
+        status
+    }
+
+    fn log_roll(&self, label: &str, res: &dice::RollResult) {
+        let character_id = self.items.get(self.selected).and_then(|c| c.id);
+        let _ = self.db.insert_roll(&mut RollRecord::new(character_id, label, res));
+    }
+
+    /// Analyzes odds prompt input: anything the roll prompt accepts, plus an optional `vs N` target.
+    fn analyze_input(&self, inp: &str) -> Result<Odds, String> {
+        let (roll, target) = match inp.rsplit_once(" vs ") {
+            Some((roll, t)) => (roll.trim(), Some(t.trim().parse::<i32>().map_err(|_| format!("invalid target '{}'", t.trim()))?)),
+            None => (inp, None),
+        };
+        let (label, expr) = self.resolve_roll(roll).map_err(|e| e.to_string())?;
+        let dist = Distribution::of(&expr).map_err(|e| e.to_string())?;
+        Ok(Odds { label: format!("{} ({})", label, expr), dist, target })
+    }
+
     fn save_current(&mut self) -> Result<()> {
         if let Some(c) = self.items.get(self.selected) {
             self.db.update_character(c)?;
@@ -374,6 +1246,9 @@ impl App {
 
     fn reload(&mut self) -> Result<()> {
         self.items = self.db.list_characters()?;
+        for c in self.items.iter_mut() {
+            if c.sync_spell_slots() { self.db.update_character(c)?; }
+        }
         if self.selected >= self.items.len() { self.selected = self.items.len().saturating_sub(1); }
         Ok(())
     }
@@ -400,8 +1275,12 @@ impl App {
                     .iter()
                     .enumerate()
                     .map(|(i, it)| {
-                        let label = format!("🧙 {} (📊 Lv.{}) - ❤️ {}/{} HP - 🛡️ AC {}", it.name, it.level, it.hp_current, it.hp_max, it.armor_class);
+                        let label = format!("🧙 {} (📊 Lv.{}) - ❤️ {} - 🛡️ AC {}", it.name, it.level, hp_text(it), it.armor_class);
                         let mut spans = vec![Span::raw(label)];
+                        if let Some((badge, color)) = vitality_badge(it) { spans.push(Span::styled(format!("  {}", badge), Style::default().fg(color).add_modifier(Modifier::BOLD))); }
+                        if let Some(spell) = &it.concentration { spans.push(Span::styled(format!("  🧠 {}", spell), Style::default().fg(Color::Cyan))); }
+                        if !it.conditions.is_empty() { spans.push(Span::styled(format!("  🌀 {}", condition_names(it)), Style::default().fg(Color::Yellow))); }
+                        if it.exhaustion > 0 { spans.push(Span::styled(format!("  😩 Exh {}", it.exhaustion), Style::default().fg(Color::Magenta))); }
                         if i == self.selected { spans.push(Span::styled("  👈", Style::default().fg(Color::Yellow))); }
                         ListItem::new(Line::from(spans))
                     })
@@ -409,7 +1288,7 @@ impl App {
                 let list = List::new(items).block(Block::default().title("🎭 Characters").borders(Borders::ALL));
                 f.render_widget(list, area);
             }
-            Mode::CreateName | Mode::CreateClass | Mode::CreateRace | Mode::CreateAbilities | Mode::CreateHpMax | Mode::CreateAc | Mode::CreateSpeed | Mode::CreateSkills | Mode::Roll => {
+            Mode::CreateName | Mode::CreateClass | Mode::CreateRace | Mode::CreateAbilities | Mode::CreateHpMax | Mode::CreateAc | Mode::CreateSpeed | Mode::CreateSkills | Mode::CreateSaves | Mode::Roll | Mode::EditMacro | Mode::EditSaves | Mode::EditHp | Mode::EditDefenses | Mode::EditResource | Mode::EditClass | Mode::AddSpell | Mode::CastSpell | Mode::ConcentrationSave | Mode::EditConditions | Mode::EditAddItem | Mode::AddItem | Mode::EditItem | Mode::SplitItem | Mode::EditPurse => {
                 let title = match self.mode {
                     Mode::CreateName => "✨ Create: Name",
                     Mode::CreateClass => "⚔️ Create: Class",
@@ -419,7 +1298,22 @@ impl App {
                     Mode::CreateAc => "🛡️ Create: Armor Class (AC)",
                     Mode::CreateSpeed => "💨 Create: Speed (ft)",
                     Mode::CreateSkills => "🎯 Create: Skills (comma or semicolon-separated)",
-                    Mode::Roll => "🎲 Roll: NdM or skill name",
+                    Mode::CreateSaves => "🛡️ Create: Saving throw proficiencies (e.g., str, con)",
+                    Mode::EditSaves => "🛡️ Saving throw proficiencies (e.g., dex, int)",
+                    Mode::EditHp => "❤️ HP: -14 or 12 fire damage (12 fire crit), +7 heal, =20 set HP, t5 temp HP, r3 max reduction (r0 clears)",
+                    Mode::EditDefenses => "🔥 Defenses: resist fire, cold; immune poison; vuln radiant",
+                    Mode::EditConditions => "🌀 Conditions: poisoned, prone, exhaustion 2 (leave out to remove)",
+                    Mode::Roll => "🎲 Roll: dice expression, macro, skill name or spell attack",
+                    Mode::EditItem => "🎒 Edit item: name | quantity | weight (lb) | value (gp, sp, cp…) | flags (e, a) | tags | description",
+                    Mode::EditPurse => "💰 Purse: +3 gp 5 sp add, -12 gp spend, 30 sp to gp convert",
+                    Mode::SplitItem => "✂️ Split: how many go into the new stack",
+                    Mode::EditAddItem | Mode::AddItem => "🎒 Item: name | quantity | weight (lb) | value (gp, sp, cp…) | flags (e, a) | tags | description",
+                    Mode::AddSpell => "✨ Spell: name | level | class | school | casting time | range | components | duration | flags (c, r) | description",
+                    Mode::CastSpell => "🔮 Cast at slot level (or p for pact slot, r for ritual)",
+                    Mode::ConcentrationSave => "🧠 Concentration save (empty, adv or dis)",
+                    Mode::EditClass => "⚔️ Class: Wizard, or Paladin 2 / Sorcerer 3 for a multiclass",
+                    Mode::EditResource => "🔋 Resource: name = max short|long, name -1, name +1, -name to delete",
+                    Mode::EditMacro => "⚡ Macro: name = expression [attack|check] or name = attack ; damage (@str..@cha, @prof, @level, @spell, @dc), -name to delete",
                     _ => unreachable!(),
                 };
                 let p = Paragraph::new(self.input.clone())
@@ -427,9 +1321,12 @@ impl App {
                     .wrap(Wrap { trim: true });
                 f.render_widget(p, area);
             }
+            Mode::ShortRest | Mode::LongRest => self.draw_rest(f, area),
+            Mode::Odds => self.draw_odds(f, area),
+            Mode::History => self.draw_history(f, area),
             _ => {
                 // Details view with tabs
-                let tabs_titles = ["📊 General", "🎯 Skills", "🎒 Inventory"].map(|t| Line::from(Span::styled(t, Style::default())));
+                let tabs_titles = ["📊 General", "🎯 Skills", "🎒 Inventory", "✨ Spells"].map(|t| Line::from(Span::styled(t, Style::default())));
                 let tabs = Tabs::new(tabs_titles)
                     .select(self.detail_tab)
                     .block(Block::default().borders(Borders::ALL).title("📖 Details"))
@@ -439,6 +1336,14 @@ impl App {
                     .constraints([Constraint::Length(3), Constraint::Min(3)])
                     .split(area);
                 f.render_widget(tabs, vchunks[0]);
+                if self.detail_tab == 3 && let Some(c) = self.items.get(self.selected) {
+                    self.draw_spells(f, vchunks[1], c);
+                    return;
+                }
+                if self.detail_tab == 2 && let Some(c) = self.items.get(self.selected) {
+                    self.draw_inventory(f, vchunks[1], c);
+                    return;
+                }
 
                 let detail = if let Some(c) = self.items.get(self.selected) {
                     match self.detail_tab {
@@ -446,7 +1351,16 @@ impl App {
                             let mut text = Vec::new();
                             text.push(Line::from(Span::styled(format!("🧙 {} the {} {} (📊 Lv.{})", c.name, c.race, c.class_name, c.level), Style::default().add_modifier(Modifier::BOLD))));
                             text.push(Line::from(""));
-                            text.push(Line::from(format!("❤️ HP {}/{}   🛡️ AC {}   💨 SPD {}", c.hp_current, c.hp_max, c.armor_class, c.speed)));
+                            text.push(Line::from(format!("❤️ {}   🛡️ AC {}   💨 SPD {}", hp_text(c), c.armor_class, speed_text(c, self.encumbrance(c)))));
+                            if let Some((badge, color)) = vitality_badge(c) { text.push(Line::from(Span::styled(badge, Style::default().fg(color).add_modifier(Modifier::BOLD)))); }
+                            if let Some(spell) = &c.concentration { text.push(Line::from(format!("🧠 Concentrating on {} (z to drop)", spell))); }
+                            if !c.conditions.is_empty() { text.push(Line::from(Span::styled(format!("🌀 Conditions: {}", condition_names(c)), Style::default().fg(Color::Yellow)))); }
+                            if c.exhaustion > 0 {
+                                let effects = EXHAUSTION_EFFECTS[..c.exhaustion as usize].join(", ");
+                                text.push(Line::from(Span::styled(format!("😩 Exhaustion {}: {}", c.exhaustion, effects), Style::default().fg(Color::Magenta))));
+                            }
+                            let defenses = format_defenses(c);
+                            if !defenses.is_empty() { text.push(Line::from(format!("🔥 {}", defenses))); }
                             text.push(Line::from(format!("💪 STR {} ({}), 🏃 DEX {} ({}), 🛡️ CON {} ({}), 🧠 INT {} ({}), 🧘 WIS {} ({}), ✨ CHA {} ({})",
                                 c.strength, Character::ability_mod(c.strength),
                                 c.dexterity, Character::ability_mod(c.dexterity),
@@ -455,14 +1369,34 @@ impl App {
                                 c.wisdom, Character::ability_mod(c.wisdom),
                                 c.charisma, Character::ability_mod(c.charisma),
                             )));
-                            text.push(Line::from(format!("🎖️ Prof bonus: +{}", c.proficiency_bonus())));
+                            text.push(Line::from(format!("🎖️ Prof bonus: +{}   🎲 Hit dice: {}", c.proficiency_bonus(), hit_dice_text(c))));
+                            text.push(Line::from(format!("🛡️ Saves: {}", ABILITIES.iter().enumerate().map(|(i, a)| {
+                                let m = c.save_modifier(a);
+                                format!("{} {}{}{}", a.to_uppercase(), if m >= 0 { "+" } else { "" }, m, if c.save_proficiencies[i] { " ⭐" } else { "" })
+                            }).collect::<Vec<_>>().join(", "))));
                             text.push(Line::from(""));
-                            text.push(Line::from("🔮 Spell slots (1-9):"));
-                            text.push(Line::from(format!("{}", c.spell_slots.iter().enumerate().map(|(i, n)| format!("✨{}:{}", i+1, n)).collect::<Vec<_>>().join("  "))));
+                            for ability in c.spellcasting_abilities() {
+                                text.push(Line::from(format!("🔮 Spell save DC {} • Spell attack {:+} ({})", c.spell_save_dc(ability), c.spell_attack_bonus(ability), ability.to_uppercase())));
+                            }
+                            text.push(Line::from(format!("🔮 Spell slots (1-9, {}):", if c.spell_slots_manual { "set by hand" } else { "from class and level" })));
+                            text.push(Line::from(slot_pips(c)));
+                            if c.pact_slots_max > 0 {
+                                text.push(Line::from(format!("🔮 Pact slots (level {}): {}", c.pact_slot_level, pips(c.pact_slots, c.pact_slots_max))));
+                            }
+                            if !c.resources.is_empty() {
+                                text.push(Line::from(""));
+                                text.push(Line::from(format!("🔋 Resources: {}", c.resources.iter().map(|r| format!("{} {}/{} ({})", capitalize(&r.name), r.current, r.max, r.recharge.label())).collect::<Vec<_>>().join(", "))));
+                            }
+                            if !c.macros.is_empty() {
+                                text.push(Line::from(""));
+                                text.push(Line::from("⚡ Macros:"));
+                                for m in &c.macros { text.push(Line::from(format!("  {} = {}{}", m.name, m.expression, m.kind.map_or("", RollKind::tag)))); }
+                            }
+                            text.push(Line::from(format!("💰 Purse: {} (= {} gp)", c.purse, c.purse.total_gp())));
                             if let Some(n) = &c.notes { text.push(Line::from("")); text.push(Line::from("📝 Notes:")); text.push(Line::from(n.clone())); }
                             Paragraph::new(text).block(Block::default().borders(Borders::ALL)).wrap(Wrap { trim: true })
                         }
-                        1 => {
+                        _ => {
                             let mut text = Vec::new();
                             text.push(Line::from("🎯 Skills:"));
                             for (name, ability) in all_skills() {
@@ -472,13 +1406,6 @@ impl App {
                             }
                             Paragraph::new(text).block(Block::default().borders(Borders::ALL)).wrap(Wrap { trim: true })
                         }
-                        _ => {
-                            let mut text = Vec::new();
-                            text.push(Line::from("🎒 Inventory:"));
-                            if c.inventory.is_empty() { text.push(Line::from("  🚧 (empty)")); }
-                            for it in &c.inventory { text.push(Line::from(format!("  🎽 {}", it))); }
-                            Paragraph::new(text).block(Block::default().borders(Borders::ALL)).wrap(Wrap { trim: true })
-                        }
                     }
                 } else {
                     Paragraph::new("No character selected").block(Block::default().borders(Borders::ALL))
@@ -488,14 +1415,408 @@ impl App {
         }
     }
 
+    /// Spellbook list with the selected spell's full text below it.
+    fn draw_spells(&self, f: &mut ratatui::Frame, area: Rect, c: &Character) {
+        let chunks = Layout::default()
+            .direction(Direction::Vertical)
+            .constraints([Constraint::Min(3), Constraint::Length(7)])
+            .split(area);
+        let items: Vec<ListItem> = self
+            .spells
+            .iter()
+            .map(|s| {
+                let mark = if s.prepared || !c.must_prepare(s) { "◉" } else { "○" };
+                let level = if s.is_cantrip() { String::from("cantrip") } else { format!("L{}", s.level) };
+                let mut tags = String::new();
+                if s.concentration { tags.push_str(" [C]"); }
+                if s.ritual { tags.push_str(" [R]"); }
+                ListItem::new(Line::from(vec![
+                    Span::styled(format!("{} {} ", mark, s.name), Style::default().add_modifier(Modifier::BOLD)),
+                    Span::raw(format!("({}{}{})", level, if s.school.is_empty() { "" } else { ", " }, s.school)),
+                    Span::styled(tags, Style::default().fg(Color::Cyan)),
+                ]))
+            })
+            .collect();
+        let title = match c.prepared_limit() {
+            Some(limit) => format!("✨ Spells • 📖 prepared {}/{} • j/k select • a add • x delete • p prepare • c cast", self.spells.iter().filter(|s| s.prepared && !s.is_cantrip()).count(), limit),
+            None => String::from("✨ Spells • j/k select • a add • x delete • c cast"),
+        };
+        let list = List::new(items)
+            .block(Block::default().title(title).borders(Borders::ALL))
+            .highlight_style(Style::default().fg(Color::Yellow))
+            .highlight_symbol("👉 ");
+        let mut state = ListState::default().with_selected((!self.spells.is_empty()).then_some(self.spell_selected));
+        f.render_stateful_widget(list, chunks[0], &mut state);
+
+        let text = match self.spells.get(self.spell_selected) {
+            Some(s) => vec![
+                Line::from(format!("⏱️ {}   📏 {}   🧩 {}   ⌛ {}", s.casting_time, s.range, s.components, s.duration)),
+                Line::from(s.description.clone()),
+            ],
+            None => vec![Line::from("🚧 No spells yet: press a to add one")],
+        };
+        f.render_widget(Paragraph::new(text).block(Block::default().borders(Borders::ALL)).wrap(Wrap { trim: true }), chunks[1]);
+    }
+
+    /// Weight carried by the selected character: items and coins.
+    fn load(&self, c: &Character) -> f64 {
+        self.inventory.iter().map(Item::total_weight).sum::<f64>() + c.purse.weight()
+    }
+
+    fn encumbrance(&self, c: &Character) -> Encumbrance {
+        c.encumbrance(self.load(c), self.variant_encumbrance)
+    }
+
+    /// Inventory table with quantity, unit and total weight and value, above the
+    /// load against carrying capacity and the selected item's description.
+    fn draw_inventory(&self, f: &mut ratatui::Frame, area: Rect, c: &Character) {
+        let chunks = Layout::default()
+            .direction(Direction::Vertical)
+            .constraints([Constraint::Min(3), Constraint::Length(4)])
+            .split(area);
+        let encumbrance = self.encumbrance(c);
+        let mut load = vec![
+            Span::raw(format!("⚖️ Load {} / {} (coins {}) • 💪 push/drag/lift {}", format_lb(self.load(c)), format_lb(c.carrying_capacity()), format_lb(c.purse.weight()), format_lb(c.push_drag_lift()))),
+        ];
+        if encumbrance != Encumbrance::Unencumbered {
+            load.push(Span::styled(format!(" • 🐢 {}: speed {}", capitalize(encumbrance.label()), speed_text(c, encumbrance)), Style::default().fg(Color::Red)));
+        }
+        let description = self.inventory.get(self.item_selected).map_or("", |it| it.description.as_str());
+        let text = vec![Line::from(load), Line::from(if description.is_empty() { "📝 No description" } else { description })];
+        f.render_widget(Paragraph::new(text).block(Block::default().borders(Borders::ALL)).wrap(Wrap { trim: true }), chunks[1]);
+
+        if self.inventory.is_empty() {
+            let p = Paragraph::new("🚧 (empty): press a to add an item").block(Block::default().title("🎒 Inventory").borders(Borders::ALL));
+            f.render_widget(p, chunks[0]);
+            return;
+        }
+        let header = Row::new(["Qty", "Item", "Weight", "Total", "Value", "Tags"]).style(Style::default().add_modifier(Modifier::BOLD));
+        let rows: Vec<Row> = self
+            .inventory
+            .iter()
+            .map(|it| {
+                let mut name = it.name.clone();
+                if it.equipped { name.push_str(" [E]"); }
+                if it.attuned { name.push_str(" [A]"); }
+                Row::new(vec![
+                    Cell::from(it.quantity.to_string()),
+                    Cell::from(name),
+                    Cell::from(format_lb(it.weight)),
+                    Cell::from(format_lb(it.total_weight())),
+                    Cell::from(format_gp(it.value * it.quantity as f64)),
+                    Cell::from(it.tags.join(", ")).style(Style::default().fg(Color::Cyan)),
+                ])
+            })
+            .collect();
+        let attuned = self.inventory.iter().filter(|it| it.attuned).count();
+        let title = format!("🎒 Inventory • 🔗 {}/3 attuned • j/k select • a add • ⏎ edit • x delete • +/- qty • s split • J/K move", attuned);
+        let widths = [Constraint::Length(5), Constraint::Min(20), Constraint::Length(9), Constraint::Length(9), Constraint::Length(11), Constraint::Min(10)];
+        let table = Table::new(rows, widths)
+            .header(header)
+            .block(Block::default().title(title).borders(Borders::ALL))
+            .highlight_style(Style::default().fg(Color::Yellow))
+            .highlight_symbol("👉 ");
+        let mut state = TableState::default().with_selected(Some(self.item_selected));
+        f.render_stateful_widget(table, chunks[0], &mut state);
+    }
+
+    /// What a rest will change, with the hit dice prompt for a short rest.
+    fn draw_rest(&self, f: &mut ratatui::Frame, area: Rect) {
+        let Some(c) = self.items.get(self.selected) else { return };
+        let short = matches!(self.mode, Mode::ShortRest);
+        let mut after = c.clone();
+        if short { after.short_rest(); } else { after.long_rest(); }
+        let mut lines: Vec<Line> = rest_changes(c, &after).into_iter().map(Line::from).collect();
+        if lines.is_empty() {
+            lines.push(Line::from(if short || c.can_long_rest() { "Nothing to recover" } else { "⚠️ Needs at least 1 HP to benefit from a long rest" }));
+        }
+        let title = if short { "🏕️ Short rest preview" } else { "🛌 Long rest preview" };
+        let preview = Paragraph::new(lines).block(Block::default().title(title).borders(Borders::ALL)).wrap(Wrap { trim: true });
+        if !short {
+            f.render_widget(preview, area);
+            return;
+        }
+        let chunks = Layout::default()
+            .direction(Direction::Vertical)
+            .constraints([Constraint::Length(3), Constraint::Min(3)])
+            .split(area);
+        let input = Paragraph::new(self.input.clone())
+            .block(Block::default().title(format!("🎲 Hit dice to spend ({} left)", hit_dice_text(c))).borders(Borders::ALL));
+        f.render_widget(input, chunks[0]);
+        f.render_widget(preview, chunks[1]);
+    }
+
+    fn draw_odds(&self, f: &mut ratatui::Frame, area: Rect) {
+        let chunks = Layout::default()
+            .direction(Direction::Vertical)
+            .constraints([Constraint::Length(3), Constraint::Length(3), Constraint::Min(5)])
+            .split(area);
+        let input = Paragraph::new(self.input.clone())
+            .block(Block::default().title("📈 Odds: roll (e.g., stealth adv, 2d6+3) and optional 'vs N'").borders(Borders::ALL));
+        f.render_widget(input, chunks[0]);
+
+        let Some(odds) = &self.odds else {
+            let hint = Paragraph::new("⏎ Enter to compute the exact distribution").block(Block::default().borders(Borders::ALL));
+            f.render_widget(hint, chunks[1].union(chunks[2]));
+            return;
+        };
+        let d = &odds.dist;
+        let mut summary = format!("📉 min {} • 📈 max {} • ⚖️ mean {:.2} • σ {:.2}", d.min(), d.max(), d.mean(), d.stddev());
+        if let Some(t) = odds.target { summary.push_str(&format!(" • 🎯 P(≥{}) = {:.1}%", t, d.prob_at_least(t) * 100.0)); }
+        f.render_widget(Paragraph::new(summary).block(Block::default().title(odds.label.clone()).borders(Borders::ALL)), chunks[1]);
+
+        // Hide the long tails of exploding dice, then group outcomes into buckets
+        // so every bar gets at least one column plus a gap.
+        let mut outcomes: Vec<(i32, f64)> = d.iter().collect();
+        while outcomes.len() > 1 && outcomes[0].1 < 0.0005 { outcomes.remove(0); }
+        while outcomes.len() > 1 && outcomes[outcomes.len() - 1].1 < 0.0005 { outcomes.pop(); }
+        let max_bars = (chunks[2].width.saturating_sub(2) / 2).max(1) as usize;
+        let bucket = outcomes.len().div_ceil(max_bars);
+        let buckets: Vec<(i32, f64)> = outcomes
+            .chunks(bucket)
+            .map(|c| (c[0].0, c.iter().map(|(_, p)| p).sum()))
+            .collect();
+        let bar_width = ((chunks[2].width.saturating_sub(2) as usize / buckets.len()).saturating_sub(1)).clamp(1, 5) as u16;
+        // Labels are clipped to the bar width, so only show them when they fit.
+        let labels = buckets.iter().all(|(v, _)| v.to_string().len() <= bar_width as usize);
+        let bars: Vec<Bar> = buckets
+            .iter()
+            .map(|(v, p)| {
+                let color = match odds.target {
+                    Some(t) if *v >= t => Color::Green,
+                    Some(_) => Color::Red,
+                    None => Color::Cyan,
+                };
+                Bar::default()
+                    .value((p * 10_000.0).round() as u64)
+                    .text_value(if bar_width >= 2 { format!("{:.0}", p * 100.0) } else { String::new() })
+                    .label(Line::from(if labels { v.to_string() } else { String::new() }))
+                    .style(Style::default().fg(color))
+            })
+            .collect();
+        let mut title = String::from("📊 Distribution (%");
+        if !labels { title.push_str(&format!(", {} to {}", outcomes[0].0, outcomes[outcomes.len() - 1].0)); }
+        if bucket > 1 { title.push_str(&format!(", {} per bar", bucket)); }
+        title.push(')');
+        let chart = BarChart::default()
+            .block(Block::default().title(title).borders(Borders::ALL))
+            .data(BarGroup::default().bars(&bars))
+            .bar_width(bar_width)
+            .bar_gap(1);
+        f.render_widget(chart, chunks[2]);
+    }
+
+    fn draw_history(&self, f: &mut ratatui::Frame, area: Rect) {
+        let items: Vec<ListItem> = self
+            .history
+            .iter()
+            .map(|r| {
+                let who = self.items.iter().find(|c| c.id.is_some() && c.id == r.character_id).map(|c| c.name.as_str()).unwrap_or("—");
+                ListItem::new(Line::from(vec![
+                    Span::styled(format!("🕒 {} ", r.timestamp), Style::default().fg(Color::DarkGray)),
+                    Span::raw(format!("🧙 {} • {}: ", who, r.label)),
+                    Span::styled(r.breakdown.clone(), dice::natural(&r.dice).map_or(Style::default(), natural_style).add_modifier(Modifier::BOLD)),
+                ]))
+            })
+            .collect();
+        let scope = match self.items.get(self.selected) {
+            Some(c) if self.history_filter => c.name.clone(),
+            _ => String::from("all characters"),
+        };
+        let list = List::new(items)
+            .block(Block::default().title(format!("📜 Roll history ({}, {} rolls)", scope, self.history.len())).borders(Borders::ALL))
+            .highlight_style(Style::default().fg(Color::Yellow))
+            .highlight_symbol("👉 ");
+        let mut state = ListState::default().with_selected(Some(self.history_selected));
+        f.render_stateful_widget(list, area, &mut state);
+    }
+
     fn draw_status(&self, f: &mut ratatui::Frame, area: Rect) {
-        let p = Paragraph::new(self.status.clone()).block(Block::default().title("Status").borders(Borders::ALL));
+        let style = match &self.last_natural {
+            Some((n, status)) if *status == self.status => natural_style(*n),
+            _ => Style::default(),
+        };
+        let p = Paragraph::new(self.status.clone()).style(style).block(Block::default().title("Status").borders(Borders::ALL));
         f.render_widget(p, area);
     }
 }
 
 fn default_status() -> String {
-    String::from("⏎ Enter: open details • 🚪 q: quit • ✨ n: new • 🗑️ d: delete • 🎲 r: roll")
+    String::from("⏎ Enter: open details • 🚪 q: quit • ✨ n: new • 🗑️ d: delete • 🎲 r: roll • 📈 o: odds • 📜 H: history • 🏕️ S/L: short/long rest • 💥 C: crit rule • ⚖️ V: encumbrance rule")
+}
+
+/// Human-readable differences between a character before and after a rest.
+fn rest_changes(before: &Character, after: &Character) -> Vec<String> {
+    let mut changes = Vec::new();
+    if hp_text(before) != hp_text(after) { changes.push(format!("❤️ {} → {}", hp_text(before), hp_text(after))); }
+    if before.hit_dice_spent != after.hit_dice_spent {
+        changes.push(format!("🎲 Hit dice {} → {}", hit_dice_text(before), hit_dice_text(after)));
+    }
+    if before.exhaustion != after.exhaustion { changes.push(format!("😩 Exhaustion {} → {}", before.exhaustion, after.exhaustion)); }
+    if before.death_successes + before.death_failures > 0 && after.death_successes + after.death_failures == 0 {
+        changes.push(String::from("💀 Death saves reset"));
+    }
+    for (i, (b, a)) in before.spell_slots.iter().zip(&after.spell_slots).enumerate().filter(|(_, (b, a))| b != a) {
+        changes.push(format!("🔮 Level {} slots {}/{} → {}/{}", i + 1, b, before.spell_slots_max[i], a, after.spell_slots_max[i]));
+    }
+    if before.pact_slots != after.pact_slots {
+        changes.push(format!("🔮 Pact slots {}/{} → {}/{}", before.pact_slots, before.pact_slots_max, after.pact_slots, after.pact_slots_max));
+    }
+    for (b, a) in before.resources.iter().zip(&after.resources).filter(|(b, a)| b.current != a.current) {
+        changes.push(format!("🔋 {} {}/{} → {}/{}", capitalize(&a.name), b.current, b.max, a.current, a.max));
+    }
+    changes
+}
+
+/// `✨1 ●●○○  ✨2 ●●` for each spell level with slots: filled pips are unspent.
+fn slot_pips(c: &Character) -> String {
+    let levels: Vec<String> = c.spell_slots_max.iter().zip(&c.spell_slots).enumerate()
+        .filter(|(_, (max, _))| **max > 0)
+        .map(|(i, (max, cur))| format!("✨{} {}", i + 1, pips(*cur, *max)))
+        .collect();
+    if levels.is_empty() { String::from("—") } else { levels.join("  ") }
+}
+
+/// `●●○○` for 2 of 4.
+fn pips(current: i32, max: i32) -> String {
+    "●".repeat(current.max(0) as usize) + &"○".repeat((max - current).max(0) as usize)
+}
+
+/// Unspent hit dice of every size, e.g. `1/1 d10 + 3/4 d6`.
+fn hit_dice_text(c: &Character) -> String {
+    c.hit_dice().iter().map(|h| h.to_string()).collect::<Vec<_>>().join(" + ")
+}
+
+/// `7/10 HP (+5 temp)`, with the HP max after any reduction.
+fn hp_text(c: &Character) -> String {
+    let mut text = format!("{}/{} HP", c.hp_current, c.effective_hp_max());
+    if c.hp_temp > 0 { text.push_str(&format!(" (+{} temp)", c.hp_temp)); }
+    if c.hp_max_reduction > 0 { text.push_str(&format!(" (max -{})", c.hp_max_reduction)); }
+    text
+}
+
+/// List/sheet badge for characters at 0 HP.
+fn vitality_badge(c: &Character) -> Option<(String, Color)> {
+    match c.vitality() {
+        Vitality::Conscious => None,
+        Vitality::Dying => Some((format!("🩸 Dying ✔{} ✖{}", c.death_successes, c.death_failures), Color::Red)),
+        Vitality::Stable => Some((String::from("🛏️ Stable"), Color::Yellow)),
+        Vitality::Dead => Some((String::from("💀 Dead"), Color::DarkGray)),
+    }
+}
+
+fn natural_style(natural: Natural) -> Style {
+    match natural {
+        Natural::Crit => Style::default().fg(Color::Green).add_modifier(Modifier::BOLD),
+        Natural::Fumble => Style::default().fg(Color::Red).add_modifier(Modifier::BOLD),
+    }
+}
+
+fn history_status() -> String {
+    String::from("📜 ↑↓ j/k: scroll • 🔎 f: toggle selected character / all • ⎋ Esc: back")
+}
+
+fn odds_status() -> String {
+    String::from("📈 Type a roll, optionally 'vs N' (e.g., 1d20+5 adv vs 16 or stealth dis vs 15) • ⏎ Enter: compute • ⎋ Esc: back")
+}
+
+/// Reads a comma/semicolon separated list of abilities into save proficiency flags.
+fn parse_saves(input: &str) -> [bool; 6] {
+    let mut flags = [false; 6];
+    for i in input.replace(';', ",").split(',').filter_map(|s| ability_index(s.trim())) {
+        flags[i] = true;
+    }
+    flags
+}
+
+fn format_saves(flags: &[bool; 6]) -> String {
+    ABILITIES.iter().zip(flags).filter(|(_, f)| **f).map(|(a, _)| *a).collect::<Vec<_>>().join(", ")
+}
+
+/// Sets resistances, vulnerabilities and immunities from input like
+/// `resist fire, cold; immune poison; vuln radiant`; unchanged on error.
+fn set_defenses(c: &mut Character, input: &str) -> Result<(), String> {
+    let (mut resist, mut vuln, mut immune) = (vec![], vec![], vec![]);
+    for part in input.split(';').map(str::trim).filter(|p| !p.is_empty()) {
+        let (word, types) = part.split_once(char::is_whitespace).unwrap_or((part, ""));
+        let list = match word.to_lowercase().as_str() {
+            "resist" | "resistant" | "resistance" => &mut resist,
+            "vuln" | "vulnerable" | "vulnerability" => &mut vuln,
+            "immune" | "immunity" => &mut immune,
+            other => return Err(format!("Start each part with resist, vuln or immune, not '{}'", other)),
+        };
+        for t in types.split(',').map(str::trim).filter(|t| !t.is_empty()) {
+            let t = DamageType::from_name(t).ok_or_else(|| format!("Unknown damage type '{}'", t))?;
+            if !list.contains(&t) { list.push(t); }
+        }
+    }
+    c.resistances = resist;
+    c.vulnerabilities = vuln;
+    c.immunities = immune;
+    Ok(())
+}
+
+fn format_defenses(c: &Character) -> String {
+    [("resist", &c.resistances), ("immune", &c.immunities), ("vuln", &c.vulnerabilities)]
+        .iter()
+        .filter(|(_, list)| !list.is_empty())
+        .map(|(word, list)| format!("{} {}", word, list.iter().map(|t| t.name()).collect::<Vec<_>>().join(", ")))
+        .collect::<Vec<_>>()
+        .join("; ")
+}
+
+/// Parses `poisoned, prone, exhaustion 2`; conditions left out are removed.
+fn set_conditions(c: &mut Character, input: &str) -> Result<(), String> {
+    let (mut conditions, mut exhaustion) = (vec![], 0);
+    for part in input.split(',').map(str::trim).filter(|p| !p.is_empty()) {
+        if let Some(level) = part.to_lowercase().strip_prefix("exhaustion") {
+            exhaustion = level.trim().parse::<i32>().ok().filter(|l| (0..=6).contains(l)).ok_or("Exhaustion level must be from 0 to 6")?;
+            continue;
+        }
+        let condition = Condition::from_name(part).ok_or_else(|| format!("Unknown condition '{}'", part))?;
+        if !conditions.contains(&condition) { conditions.push(condition); }
+    }
+    c.conditions = conditions;
+    c.set_exhaustion(exhaustion);
+    Ok(())
+}
+
+fn format_conditions(c: &Character) -> String {
+    let mut parts: Vec<String> = c.conditions.iter().map(|cond| cond.name().to_string()).collect();
+    if c.exhaustion > 0 { parts.push(format!("exhaustion {}", c.exhaustion)); }
+    parts.join(", ")
+}
+
+fn condition_names(c: &Character) -> String {
+    c.conditions.iter().map(|cond| cond.name()).collect::<Vec<_>>().join(", ")
+}
+
+/// `15 (30)` when encumbrance, conditions or exhaustion slow the character down.
+fn speed_text(c: &Character, load: Encumbrance) -> String {
+    let speed = c.effective_speed(load);
+    if speed == c.speed { speed.to_string() } else { format!("{} ({})", speed, c.speed) }
+}
+
+/// `2.5 lb`, rounded to hundredths.
+fn format_lb(lb: f64) -> String {
+    format!("{} lb", (lb * 100.0).round() / 100.0)
+}
+
+/// `15 gp`, or `—` for no value.
+fn format_gp(gp: f64) -> String {
+    if gp == 0.0 { String::from("—") } else { format!("{} gp", (gp * 100.0).round() / 100.0) }
+}
+
+/// An item in the form [`Item::parse`] reads, for editing.
+fn format_item(it: &Item) -> String {
+    let flags = format!("{}{}", if it.equipped { "e" } else { "" }, if it.attuned { "a" } else { "" });
+    let weight = if it.weight == 0.0 { String::new() } else { it.weight.to_string() };
+    let value = if it.value == 0.0 { String::new() } else { format!("{} gp", it.value) };
+    [it.name.clone(), it.quantity.to_string(), weight, value, flags, it.tags.join(", "), it.description.clone()].join(" | ")
+}
+
+fn add_item_status() -> String {
+    String::from("🎒 Type name | quantity | weight | value | flags (e, a) | tags | description (only the name is required), then ⏎ Enter. ⎋ Esc cancel")
 }
 
 fn capitalize(s: &str) -> String {
@@ -504,11 +1825,18 @@ fn capitalize(s: &str) -> String {
 }
 
 fn details_status() -> String {
-    String::from("←→ Arrows/Tabs: switch tabs • ✏️ e: edit • 🎲 r: roll • ⎋ Esc: back")
+    String::from("←→ Arrows/Tabs: switch tabs • ✏️ e: edit • 🎲 r: roll • 📈 o: odds • 📜 H: history • 🏕️ S/L: short/long rest • ⎋ Esc: back")
 }
 
 fn edit_status() -> String {
-    String::from("✏️ Editing: ❤️ +/- hp • 🔮 [/] adjust slot • 🔢 1-9 select • 🎒 a/A add/remove item • 📈 l level up • 💾 s save • ⎋ Esc: back")
+    String::from("✏️ Editing: ❤️ +/- hp • 🔮 [/] spend/regain slot • {/} slot max • 🔁 o auto slots • 🔢 1-9 select, 0 pact • 🎒 a/A add/remove item • ⚡ m macro • 🛡️ v saves • ⚔️ k class • ❤️ p hp amount • 🔥 R defenses • 🌀 n conditions • 💰 $ purse • 🔋 c resource • 📈 l level up • 🩹 x clear death saves • 💾 s save • ⎋ Esc: back")
+}
+
+/// Last computed distribution shown in [`Mode::Odds`].
+struct Odds {
+    label: String,
+    dist: Distribution,
+    target: Option<i32>,
 }
 
 #[derive(Default, Clone)]
@@ -1,8 +1,15 @@
 use anyhow::Result;
-use rusqlite::{params, Connection, OptionalExtension};
-use serde_json;
+use rusqlite::{params, Connection, OptionalExtension, Row};
 
-use crate::models::Character;
+use crate::models::{Character, Item, RecordKind, RollRecord, Spell};
+
+/// Columns read back into a [`Character`], in `SELECT` order.
+const CHARACTER_COLUMNS: &str = "id, name, class_name, race, level, hp_current, hp_max, armor_class, speed,
+    strength, dexterity, constitution, intelligence, wisdom, charisma,
+    spell_slots, skill_proficiencies, notes, macros, save_proficiencies,
+    death_successes, death_failures, stable, hp_temp, hp_max_reduction, resistances, vulnerabilities,
+    immunities, hit_dice_spent, resources, spell_slots_max, spell_slots_manual, pact_slots,
+    pact_slots_max, pact_slot_level, concentration, conditions, exhaustion, purse";
 
 pub struct Db {
     conn: Connection,
@@ -37,24 +44,153 @@ impl Db {
                 wisdom INTEGER NOT NULL,
                 charisma INTEGER NOT NULL,
                 spell_slots TEXT NOT NULL,
-                inventory TEXT NOT NULL,
                 skill_proficiencies TEXT NOT NULL,
                 notes TEXT
             );
+            CREATE TABLE IF NOT EXISTS rolls (
+                id INTEGER PRIMARY KEY AUTOINCREMENT,
+                character_id INTEGER REFERENCES characters(id) ON DELETE CASCADE,
+                timestamp TEXT NOT NULL DEFAULT (datetime('now', 'localtime')),
+                kind TEXT NOT NULL DEFAULT 'roll',
+                label TEXT NOT NULL,
+                expression TEXT NOT NULL,
+                dice TEXT NOT NULL,
+                modifier INTEGER NOT NULL,
+                total INTEGER NOT NULL,
+                breakdown TEXT NOT NULL
+            );
+            CREATE INDEX IF NOT EXISTS rolls_character ON rolls(character_id);
+            CREATE TABLE IF NOT EXISTS spells (
+                id INTEGER PRIMARY KEY AUTOINCREMENT,
+                character_id INTEGER NOT NULL REFERENCES characters(id) ON DELETE CASCADE,
+                name TEXT NOT NULL,
+                level INTEGER NOT NULL,
+                class TEXT NOT NULL DEFAULT '',
+                school TEXT NOT NULL DEFAULT '',
+                casting_time TEXT NOT NULL DEFAULT '',
+                range TEXT NOT NULL DEFAULT '',
+                components TEXT NOT NULL DEFAULT '',
+                duration TEXT NOT NULL DEFAULT '',
+                concentration INTEGER NOT NULL DEFAULT 0,
+                ritual INTEGER NOT NULL DEFAULT 0,
+                description TEXT NOT NULL DEFAULT '',
+                prepared INTEGER NOT NULL DEFAULT 0
+            );
+            CREATE INDEX IF NOT EXISTS spells_character ON spells(character_id);
+            CREATE TABLE IF NOT EXISTS items (
+                id INTEGER PRIMARY KEY AUTOINCREMENT,
+                character_id INTEGER NOT NULL REFERENCES characters(id) ON DELETE CASCADE,
+                name TEXT NOT NULL,
+                quantity INTEGER NOT NULL DEFAULT 1,
+                weight REAL NOT NULL DEFAULT 0,
+                value REAL NOT NULL DEFAULT 0,
+                description TEXT NOT NULL DEFAULT '',
+                equipped INTEGER NOT NULL DEFAULT 0,
+                attuned INTEGER NOT NULL DEFAULT 0,
+                tags TEXT NOT NULL DEFAULT '[]',
+                position INTEGER NOT NULL DEFAULT 0
+            );
+            CREATE INDEX IF NOT EXISTS items_character ON items(character_id);
+            CREATE TABLE IF NOT EXISTS settings (
+                key TEXT PRIMARY KEY,
+                value TEXT NOT NULL
+            );
             "#,
         )?;
+        self.migrate()?;
+        Ok(())
+    }
+
+    /// Adds columns introduced after the first release to existing databases.
+    fn migrate(&self) -> Result<()> {
+        self.add_column_if_missing("characters", "macros", "TEXT NOT NULL DEFAULT '[]'")?;
+        self.add_column_if_missing("characters", "save_proficiencies", "TEXT NOT NULL DEFAULT '[false,false,false,false,false,false]'")?;
+        self.add_column_if_missing("characters", "death_successes", "INTEGER NOT NULL DEFAULT 0")?;
+        self.add_column_if_missing("characters", "death_failures", "INTEGER NOT NULL DEFAULT 0")?;
+        self.add_column_if_missing("characters", "stable", "INTEGER NOT NULL DEFAULT 0")?;
+        self.add_column_if_missing("characters", "hp_temp", "INTEGER NOT NULL DEFAULT 0")?;
+        self.add_column_if_missing("characters", "hp_max_reduction", "INTEGER NOT NULL DEFAULT 0")?;
+        self.add_column_if_missing("characters", "resistances", "TEXT NOT NULL DEFAULT '[]'")?;
+        self.add_column_if_missing("characters", "vulnerabilities", "TEXT NOT NULL DEFAULT '[]'")?;
+        self.add_column_if_missing("characters", "immunities", "TEXT NOT NULL DEFAULT '[]'")?;
+        // Existing characters start with all their hit dice.
+        self.add_column_if_missing("characters", "hit_dice_spent", "TEXT NOT NULL DEFAULT '{}'")?;
+        self.add_column_if_missing("characters", "resources", "TEXT NOT NULL DEFAULT '[]'")?;
+        if self.add_column_if_missing("characters", "spell_slots_max", "TEXT NOT NULL DEFAULT '[0,0,0,0,0,0,0,0,0]'")? {
+            // Slots used to be a single number per level: treat it as both max and current.
+            self.conn.execute("UPDATE characters SET spell_slots_max = spell_slots", [])?;
+        }
+        if self.add_column_if_missing("characters", "spell_slots_manual", "INTEGER NOT NULL DEFAULT 0")? {
+            // Keep slots that were typed in by hand.
+            self.conn.execute("UPDATE characters SET spell_slots_manual = 1 WHERE spell_slots_max <> '[0,0,0,0,0,0,0,0,0]'", [])?;
+        }
+        self.add_column_if_missing("characters", "pact_slots", "INTEGER NOT NULL DEFAULT 0")?;
+        self.add_column_if_missing("characters", "pact_slots_max", "INTEGER NOT NULL DEFAULT 0")?;
+        self.add_column_if_missing("characters", "pact_slot_level", "INTEGER NOT NULL DEFAULT 0")?;
+        self.add_column_if_missing("characters", "concentration", "TEXT")?;
+        self.add_column_if_missing("characters", "conditions", "TEXT NOT NULL DEFAULT '[]'")?;
+        self.add_column_if_missing("characters", "exhaustion", "INTEGER NOT NULL DEFAULT 0")?;
+        self.add_column_if_missing("characters", "purse", "TEXT NOT NULL DEFAULT '{}'")?;
+        if self.has_column("characters", "inventory")? {
+            self.migrate_inventory()?;
+        }
+        Ok(())
+    }
+
+    /// Moves the old JSON list of item names into the `items` table, one of each.
+    fn migrate_inventory(&self) -> Result<()> {
+        let tx = self.conn.unchecked_transaction()?;
+        let inventories = {
+            let mut stmt = tx.prepare("SELECT id, inventory FROM characters")?;
+            let rows = stmt.query_map([], |row| Ok((row.get::<_, i64>(0)?, row.get::<_, String>(1)?)))?;
+            rows.collect::<rusqlite::Result<Vec<_>>>()?
+        };
+        for (character_id, inventory) in inventories {
+            let names: Vec<String> = serde_json::from_str(&inventory).unwrap_or_default();
+            for name in names.iter().filter(|n| !n.trim().is_empty()) {
+                let mut item = Item::named(name);
+                item.character_id = Some(character_id);
+                self.insert_item(&mut item)?;
+            }
+        }
+        tx.execute_batch("ALTER TABLE characters DROP COLUMN inventory;")?;
+        tx.commit()?;
         Ok(())
     }
 
+    fn has_column(&self, table: &str, column: &str) -> Result<bool> {
+        let mut stmt = self.conn.prepare(&format!("PRAGMA table_info({})", table))?;
+        let exists = stmt
+            .query_map([], |row| row.get::<_, String>(1))?
+            .collect::<rusqlite::Result<Vec<_>>>()?
+            .iter()
+            .any(|c| c == column);
+        Ok(exists)
+    }
+
+    /// Adds a column to an existing table; returns whether it had to be added.
+    fn add_column_if_missing(&self, table: &str, column: &str, decl: &str) -> Result<bool> {
+        let exists = self.has_column(table, column)?;
+        if !exists {
+            self.conn.execute_batch(&format!("ALTER TABLE {} ADD COLUMN {} {};", table, column, decl))?;
+        }
+        Ok(!exists)
+    }
+
     pub fn insert_character(&self, character: &mut Character) -> Result<i64> {
         let spell_slots = serde_json::to_string(&character.spell_slots)?;
-        let inventory = serde_json::to_string(&character.inventory)?;
         self.conn.execute(
             r#"INSERT INTO characters
                 (name, class_name, race, level, hp_current, hp_max, armor_class, speed,
                  strength, dexterity, constitution, intelligence, wisdom, charisma,
-                 spell_slots, inventory, skill_proficiencies, notes)
-               VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10, ?11, ?12, ?13, ?14, ?15, ?16, ?17, ?18)
+                 spell_slots, skill_proficiencies, notes, macros, save_proficiencies,
+                 death_successes, death_failures, stable, hp_temp, hp_max_reduction, resistances,
+                 vulnerabilities, immunities, hit_dice_spent, resources, spell_slots_max,
+                 spell_slots_manual, pact_slots, pact_slots_max, pact_slot_level, concentration,
+                 conditions, exhaustion, purse)
+               VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10, ?11, ?12, ?13, ?14, ?15, ?16, ?17, ?18, ?19, ?20,
+                       ?21, ?22, ?23, ?24, ?25, ?26, ?27, ?28, ?29, ?30, ?31, ?32,
+                       ?33, ?34, ?35, ?36, ?37, ?38)
             "#,
             params![
                 character.name,
@@ -72,9 +208,29 @@ impl Db {
                 character.wisdom,
                 character.charisma,
                 spell_slots,
-                inventory,
                 serde_json::to_string(&character.skill_proficiencies)?,
                 character.notes,
+                serde_json::to_string(&character.macros)?,
+                serde_json::to_string(&character.save_proficiencies)?,
+                character.death_successes,
+                character.death_failures,
+                character.stable,
+                character.hp_temp,
+                character.hp_max_reduction,
+                serde_json::to_string(&character.resistances)?,
+                serde_json::to_string(&character.vulnerabilities)?,
+                serde_json::to_string(&character.immunities)?,
+                serde_json::to_string(&character.hit_dice_spent)?,
+                serde_json::to_string(&character.resources)?,
+                serde_json::to_string(&character.spell_slots_max)?,
+                character.spell_slots_manual,
+                character.pact_slots,
+                character.pact_slots_max,
+                character.pact_slot_level,
+                character.concentration,
+                serde_json::to_string(&character.conditions)?,
+                character.exhaustion,
+                serde_json::to_string(&character.purse)?,
             ],
         )?;
         let id = self.conn.last_insert_rowid();
@@ -85,14 +241,19 @@ impl Db {
     pub fn update_character(&self, character: &Character) -> Result<()> {
         let id = character.id.expect("character must have id to update");
         let spell_slots = serde_json::to_string(&character.spell_slots)?;
-        let inventory = serde_json::to_string(&character.inventory)?;
         self.conn.execute(
             r#"UPDATE characters SET
                 name = ?1, class_name = ?2, race = ?3, level = ?4, hp_current = ?5,
                 hp_max = ?6, armor_class = ?7, speed = ?8, strength = ?9, dexterity = ?10,
                 constitution = ?11, intelligence = ?12, wisdom = ?13, charisma = ?14,
-                spell_slots = ?15, inventory = ?16, skill_proficiencies = ?17, notes = ?18
-               WHERE id = ?19
+                spell_slots = ?15, skill_proficiencies = ?16, notes = ?17,
+                macros = ?18, save_proficiencies = ?19, death_successes = ?20, death_failures = ?21,
+                stable = ?22, hp_temp = ?23, hp_max_reduction = ?24, resistances = ?25,
+                vulnerabilities = ?26, immunities = ?27, hit_dice_spent = ?28,
+                resources = ?29, spell_slots_max = ?30, spell_slots_manual = ?31,
+                pact_slots = ?32, pact_slots_max = ?33, pact_slot_level = ?34, concentration = ?35,
+                conditions = ?36, exhaustion = ?37, purse = ?38
+               WHERE id = ?39
             "#,
             params![
                 character.name,
@@ -110,9 +271,29 @@ impl Db {
                 character.wisdom,
                 character.charisma,
                 spell_slots,
-                inventory,
                 serde_json::to_string(&character.skill_proficiencies)?,
                 character.notes,
+                serde_json::to_string(&character.macros)?,
+                serde_json::to_string(&character.save_proficiencies)?,
+                character.death_successes,
+                character.death_failures,
+                character.stable,
+                character.hp_temp,
+                character.hp_max_reduction,
+                serde_json::to_string(&character.resistances)?,
+                serde_json::to_string(&character.vulnerabilities)?,
+                serde_json::to_string(&character.immunities)?,
+                serde_json::to_string(&character.hit_dice_spent)?,
+                serde_json::to_string(&character.resources)?,
+                serde_json::to_string(&character.spell_slots_max)?,
+                character.spell_slots_manual,
+                character.pact_slots,
+                character.pact_slots_max,
+                character.pact_slot_level,
+                character.concentration,
+                serde_json::to_string(&character.conditions)?,
+                character.exhaustion,
+                serde_json::to_string(&character.purse)?,
                 id
             ],
         )?;
@@ -126,75 +307,268 @@ impl Db {
     }
 
     pub fn get_character(&self, id: i64) -> Result<Option<Character>> {
+        let mut stmt = self
+            .conn
+            .prepare(&format!("SELECT {} FROM characters WHERE id = ?1", CHARACTER_COLUMNS))?;
+        let row = stmt.query_row(params![id], character_from_row).optional()?;
+        Ok(row)
+    }
+
+    pub fn list_characters(&self) -> Result<Vec<Character>> {
+        let mut stmt = self
+            .conn
+            .prepare(&format!("SELECT {} FROM characters ORDER BY name ASC", CHARACTER_COLUMNS))?;
+
+        let rows = stmt.query_map([], character_from_row)?;
+
+        let mut result = Vec::new();
+        for r in rows {
+            result.push(r?);
+        }
+        Ok(result)
+    }
+
+    pub fn get_setting(&self, key: &str) -> Result<Option<String>> {
+        let value = self
+            .conn
+            .query_row("SELECT value FROM settings WHERE key = ?1", params![key], |row| row.get(0))
+            .optional()?;
+        Ok(value)
+    }
+
+    pub fn set_setting(&self, key: &str, value: &str) -> Result<()> {
+        self.conn.execute(
+            "INSERT INTO settings (key, value) VALUES (?1, ?2) ON CONFLICT(key) DO UPDATE SET value = excluded.value",
+            params![key, value],
+        )?;
+        Ok(())
+    }
+
+    pub fn insert_roll(&self, record: &mut RollRecord) -> Result<i64> {
+        self.conn.execute(
+            r#"INSERT INTO rolls (character_id, kind, label, expression, dice, modifier, total, breakdown)
+               VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8)
+            "#,
+            params![
+                record.character_id,
+                record.kind.key(),
+                record.label,
+                record.expression,
+                serde_json::to_string(&record.dice)?,
+                record.modifier,
+                record.total,
+                record.breakdown,
+            ],
+        )?;
+        let id = self.conn.last_insert_rowid();
+        record.id = Some(id);
+        record.timestamp = self
+            .conn
+            .query_row("SELECT timestamp FROM rolls WHERE id = ?1", params![id], |row| row.get(0))?;
+        Ok(id)
+    }
+
+    /// Most recent rolls first, optionally only those of one character.
+    pub fn list_rolls(&self, character_id: Option<i64>) -> Result<Vec<RollRecord>> {
         let mut stmt = self.conn.prepare(
-            r#"SELECT id, name, class_name, race, level, hp_current, hp_max, armor_class, speed,
-                      strength, dexterity, constitution, intelligence, wisdom, charisma,
-                      spell_slots, inventory, skill_proficiencies, notes
-                 FROM characters WHERE id = ?1"#,
+            r#"SELECT id, character_id, timestamp, kind, label, expression, dice, modifier, total, breakdown
+                 FROM rolls WHERE ?1 IS NULL OR character_id = ?1 ORDER BY id DESC"#,
         )?;
-        let row = stmt
-            .query_row(params![id], |row| {
-                let spell_slots: String = row.get(15)?;
-                let inventory: String = row.get(16)?;
-                let skills: String = row.get(17)?;
-                Ok(Character {
-                    id: row.get(0)?,
-                    name: row.get(1)?,
-                    class_name: row.get(2)?,
-                    race: row.get(3)?,
-                    level: row.get(4)?,
-                    hp_current: row.get(5)?,
-                    hp_max: row.get(6)?,
-                    armor_class: row.get(7)?,
-                    speed: row.get(8)?,
-                    strength: row.get(9)?,
-                    dexterity: row.get(10)?,
-                    constitution: row.get(11)?,
-                    intelligence: row.get(12)?,
-                    wisdom: row.get(13)?,
-                    charisma: row.get(14)?,
-                    spell_slots: serde_json::from_str(&spell_slots).unwrap_or_else(|_| vec![0; 9]),
-                    inventory: serde_json::from_str(&inventory).unwrap_or_default(),
-                    skill_proficiencies: serde_json::from_str(&skills).unwrap_or_default(),
-                    notes: row.get(18).ok(),
-                })
+
+        let rows = stmt.query_map(params![character_id], |row| {
+            let kind: String = row.get(3)?;
+            let dice: String = row.get(6)?;
+            Ok(RollRecord {
+                id: row.get(0)?,
+                character_id: row.get(1)?,
+                timestamp: row.get(2)?,
+                kind: RecordKind::from_key(&kind),
+                label: row.get(4)?,
+                expression: row.get(5)?,
+                dice: serde_json::from_str(&dice).unwrap_or_default(),
+                modifier: row.get(7)?,
+                total: row.get(8)?,
+                breakdown: row.get(9)?,
             })
-            .optional()?;
-        Ok(row)
+        })?;
+
+        let mut result = Vec::new();
+        for r in rows {
+            result.push(r?);
+        }
+        Ok(result)
     }
 
-    pub fn list_characters(&self) -> Result<Vec<Character>> {
+    pub fn insert_spell(&self, spell: &mut Spell) -> Result<i64> {
+        self.conn.execute(
+            r#"INSERT INTO spells (character_id, name, level, class, school, casting_time, range, components,
+                 duration, concentration, ritual, description, prepared)
+               VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10, ?11, ?12, ?13)
+            "#,
+            params![
+                spell.character_id,
+                spell.name,
+                spell.level,
+                spell.class,
+                spell.school,
+                spell.casting_time,
+                spell.range,
+                spell.components,
+                spell.duration,
+                spell.concentration,
+                spell.ritual,
+                spell.description,
+                spell.prepared,
+            ],
+        )?;
+        let id = self.conn.last_insert_rowid();
+        spell.id = Some(id);
+        Ok(id)
+    }
+
+    pub fn update_spell(&self, spell: &Spell) -> Result<()> {
+        let id = spell.id.expect("spell must have id to update");
+        self.conn.execute(
+            r#"UPDATE spells SET
+                name = ?1, level = ?2, class = ?3, school = ?4, casting_time = ?5, range = ?6, components = ?7,
+                duration = ?8, concentration = ?9, ritual = ?10, description = ?11, prepared = ?12
+               WHERE id = ?13"#,
+            params![
+                spell.name,
+                spell.level,
+                spell.class,
+                spell.school,
+                spell.casting_time,
+                spell.range,
+                spell.components,
+                spell.duration,
+                spell.concentration,
+                spell.ritual,
+                spell.description,
+                spell.prepared,
+                id
+            ],
+        )?;
+        Ok(())
+    }
+
+    pub fn delete_spell(&self, id: i64) -> Result<()> {
+        self.conn.execute("DELETE FROM spells WHERE id = ?1", params![id])?;
+        Ok(())
+    }
+
+    /// Spells of a character by level, then name.
+    pub fn list_spells(&self, character_id: i64) -> Result<Vec<Spell>> {
         let mut stmt = self.conn.prepare(
-            r#"SELECT id, name, class_name, race, level, hp_current, hp_max, armor_class, speed,
-                      strength, dexterity, constitution, intelligence, wisdom, charisma,
-                      spell_slots, inventory, skill_proficiencies, notes
-                 FROM characters ORDER BY name ASC"#,
+            r#"SELECT id, character_id, name, level, class, school, casting_time, range, components, duration,
+                      concentration, ritual, description, prepared
+                 FROM spells WHERE character_id = ?1 ORDER BY level, name COLLATE NOCASE"#,
+        )?;
+        let rows = stmt.query_map(params![character_id], |row| {
+            Ok(Spell {
+                id: row.get(0)?,
+                character_id: row.get(1)?,
+                name: row.get(2)?,
+                level: row.get(3)?,
+                class: row.get(4)?,
+                school: row.get(5)?,
+                casting_time: row.get(6)?,
+                range: row.get(7)?,
+                components: row.get(8)?,
+                duration: row.get(9)?,
+                concentration: row.get(10)?,
+                ritual: row.get(11)?,
+                description: row.get(12)?,
+                prepared: row.get(13)?,
+            })
+        })?;
+
+        let mut result = Vec::new();
+        for r in rows {
+            result.push(r?);
+        }
+        Ok(result)
+    }
+
+    pub fn insert_item(&self, item: &mut Item) -> Result<i64> {
+        self.conn.execute(
+            r#"INSERT INTO items (character_id, name, quantity, weight, value, description, equipped, attuned, tags, position)
+               VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9,
+                       (SELECT COALESCE(MAX(position), 0) + 1 FROM items WHERE character_id = ?1))
+            "#,
+            params![
+                item.character_id,
+                item.name,
+                item.quantity,
+                item.weight,
+                item.value,
+                item.description,
+                item.equipped,
+                item.attuned,
+                serde_json::to_string(&item.tags)?,
+            ],
         )?;
+        let id = self.conn.last_insert_rowid();
+        item.id = Some(id);
+        Ok(id)
+    }
 
-        let rows = stmt.query_map([], |row| {
-            let spell_slots: String = row.get(15)?;
-            let inventory: String = row.get(16)?;
-            let skills: String = row.get(17)?;
-            Ok(Character {
+    pub fn update_item(&self, item: &Item) -> Result<()> {
+        let id = item.id.expect("item must have id to update");
+        self.conn.execute(
+            r#"UPDATE items SET
+                name = ?1, quantity = ?2, weight = ?3, value = ?4, description = ?5,
+                equipped = ?6, attuned = ?7, tags = ?8
+               WHERE id = ?9"#,
+            params![
+                item.name,
+                item.quantity,
+                item.weight,
+                item.value,
+                item.description,
+                item.equipped,
+                item.attuned,
+                serde_json::to_string(&item.tags)?,
+                id
+            ],
+        )?;
+        Ok(())
+    }
+
+    pub fn delete_item(&self, id: i64) -> Result<()> {
+        self.conn.execute("DELETE FROM items WHERE id = ?1", params![id])?;
+        Ok(())
+    }
+
+    /// Stores the inventory order: each item's position is its index in `ids`.
+    pub fn reorder_items(&self, ids: &[i64]) -> Result<()> {
+        let tx = self.conn.unchecked_transaction()?;
+        for (position, id) in ids.iter().enumerate() {
+            tx.execute("UPDATE items SET position = ?1 WHERE id = ?2", params![position as i64, id])?;
+        }
+        tx.commit()?;
+        Ok(())
+    }
+
+    /// Items of a character in inventory order.
+    pub fn list_items(&self, character_id: i64) -> Result<Vec<Item>> {
+        let mut stmt = self.conn.prepare(
+            r#"SELECT id, character_id, name, quantity, weight, value, description, equipped, attuned, tags
+                 FROM items WHERE character_id = ?1 ORDER BY position, id"#,
+        )?;
+        let rows = stmt.query_map(params![character_id], |row| {
+            let tags: String = row.get(9)?;
+            Ok(Item {
                 id: row.get(0)?,
-                name: row.get(1)?,
-                class_name: row.get(2)?,
-                race: row.get(3)?,
-                level: row.get(4)?,
-                hp_current: row.get(5)?,
-                hp_max: row.get(6)?,
-                armor_class: row.get(7)?,
-                speed: row.get(8)?,
-                strength: row.get(9)?,
-                dexterity: row.get(10)?,
-                constitution: row.get(11)?,
-                intelligence: row.get(12)?,
-                wisdom: row.get(13)?,
-                charisma: row.get(14)?,
-                spell_slots: serde_json::from_str(&spell_slots).unwrap_or_else(|_| vec![0; 9]),
-                inventory: serde_json::from_str(&inventory).unwrap_or_default(),
-                skill_proficiencies: serde_json::from_str(&skills).unwrap_or_default(),
-                notes: row.get(18).ok(),
+                character_id: row.get(1)?,
+                name: row.get(2)?,
+                quantity: row.get(3)?,
+                weight: row.get(4)?,
+                value: row.get(5)?,
+                description: row.get(6)?,
+                equipped: row.get(7)?,
+                attuned: row.get(8)?,
+                tags: serde_json::from_str(&tags).unwrap_or_default(),
             })
         })?;
 
@@ -205,3 +579,177 @@ impl Db {
         Ok(result)
     }
 }
+
+fn character_from_row(row: &Row) -> rusqlite::Result<Character> {
+    let spell_slots: String = row.get("spell_slots")?;
+    let skills: String = row.get("skill_proficiencies")?;
+    let macros: String = row.get("macros")?;
+    let saves: String = row.get("save_proficiencies")?;
+    let resistances: String = row.get("resistances")?;
+    let vulnerabilities: String = row.get("vulnerabilities")?;
+    let immunities: String = row.get("immunities")?;
+    let hit_dice_spent: String = row.get("hit_dice_spent")?;
+    let resources: String = row.get("resources")?;
+    let spell_slots_max: String = row.get("spell_slots_max")?;
+    let conditions: String = row.get("conditions")?;
+    let purse: String = row.get("purse")?;
+    Ok(Character {
+        id: row.get("id")?,
+        name: row.get("name")?,
+        class_name: row.get("class_name")?,
+        race: row.get("race")?,
+        level: row.get("level")?,
+        hp_current: row.get("hp_current")?,
+        hp_max: row.get("hp_max")?,
+        death_successes: row.get("death_successes")?,
+        death_failures: row.get("death_failures")?,
+        stable: row.get("stable")?,
+        hp_temp: row.get("hp_temp")?,
+        hp_max_reduction: row.get("hp_max_reduction")?,
+        hit_dice_spent: serde_json::from_str(&hit_dice_spent).unwrap_or_default(),
+        armor_class: row.get("armor_class")?,
+        speed: row.get("speed")?,
+        strength: row.get("strength")?,
+        dexterity: row.get("dexterity")?,
+        constitution: row.get("constitution")?,
+        intelligence: row.get("intelligence")?,
+        wisdom: row.get("wisdom")?,
+        charisma: row.get("charisma")?,
+        spell_slots: serde_json::from_str(&spell_slots).unwrap_or_else(|_| vec![0; 9]),
+        spell_slots_max: serde_json::from_str(&spell_slots_max).unwrap_or_else(|_| vec![0; 9]),
+        spell_slots_manual: row.get("spell_slots_manual")?,
+        pact_slots: row.get("pact_slots")?,
+        pact_slots_max: row.get("pact_slots_max")?,
+        pact_slot_level: row.get("pact_slot_level")?,
+        concentration: row.get("concentration")?,
+        conditions: serde_json::from_str(&conditions).unwrap_or_default(),
+        exhaustion: row.get("exhaustion")?,
+        purse: serde_json::from_str(&purse).unwrap_or_default(),
+        skill_proficiencies: serde_json::from_str(&skills).unwrap_or_default(),
+        notes: row.get("notes").ok(),
+        macros: serde_json::from_str(&macros).unwrap_or_default(),
+        save_proficiencies: serde_json::from_str(&saves).unwrap_or_default(),
+        resistances: serde_json::from_str(&resistances).unwrap_or_default(),
+        vulnerabilities: serde_json::from_str(&vulnerabilities).unwrap_or_default(),
+        immunities: serde_json::from_str(&immunities).unwrap_or_default(),
+        resources: serde_json::from_str(&resources).unwrap_or_default(),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::PathBuf;
+
+    use super::*;
+
+    #[test]
+    fn saves_and_loads_characters() {
+        let db = Db::open_or_create(":memory:").unwrap();
+        let mut c = Character { name: String::from("Bo"), ..Character::default() };
+        c.set_class("Fighter 1 / Wizard 4");
+        c.spend_hit_dice(2, None);
+        c.purse.add(12, crate::models::Coin::Gp).unwrap();
+        let id = db.insert_character(&mut c).unwrap();
+        c.hp_current = 3;
+        db.update_character(&c).unwrap();
+
+        let loaded = db.get_character(id).unwrap().unwrap();
+        assert_eq!(loaded.id, Some(id));
+        assert_eq!(loaded.hp_current, 3);
+        assert_eq!(loaded.hit_dice_spent, c.hit_dice_spent);
+        assert_eq!(loaded.hit_dice(), c.hit_dice());
+        assert_eq!(loaded.purse, c.purse);
+        assert!(db.get_character(id + 1).unwrap().is_none());
+    }
+
+    #[test]
+    fn logs_casts_apart_from_rolls() {
+        let db = Db::open_or_create(":memory:").unwrap();
+        let spell = Spell::parse("Shield | 1").unwrap();
+        db.insert_roll(&mut RollRecord::cast(None, &spell, "casts Shield")).unwrap();
+        let cast = &db.list_rolls(None).unwrap()[0];
+        assert_eq!(cast.kind, RecordKind::Cast);
+        assert_eq!((cast.expression.as_str(), cast.total), ("", 0));
+    }
+
+    /// A database file that's removed when dropped.
+    struct TempDb(PathBuf);
+
+    impl TempDb {
+        fn new(name: &str) -> Self {
+            let path = std::env::temp_dir().join(format!("shito-{}-{}.sqlite3", name, std::process::id()));
+            let _ = std::fs::remove_file(&path);
+            Self(path)
+        }
+
+        fn path(&self) -> &str {
+            self.0.to_str().unwrap()
+        }
+    }
+
+    impl Drop for TempDb {
+        fn drop(&mut self) {
+            let _ = std::fs::remove_file(&self.0);
+        }
+    }
+
+    /// The `characters` table of the first release.
+    fn create_old_schema(path: &str) {
+        let conn = Connection::open(path).unwrap();
+        conn.execute_batch(
+            r#"
+            CREATE TABLE characters (
+                id INTEGER PRIMARY KEY AUTOINCREMENT,
+                name TEXT NOT NULL,
+                class_name TEXT NOT NULL,
+                race TEXT NOT NULL,
+                level INTEGER NOT NULL,
+                hp_current INTEGER NOT NULL,
+                hp_max INTEGER NOT NULL,
+                armor_class INTEGER NOT NULL,
+                speed INTEGER NOT NULL,
+                strength INTEGER NOT NULL,
+                dexterity INTEGER NOT NULL,
+                constitution INTEGER NOT NULL,
+                intelligence INTEGER NOT NULL,
+                wisdom INTEGER NOT NULL,
+                charisma INTEGER NOT NULL,
+                spell_slots TEXT NOT NULL,
+                inventory TEXT NOT NULL,
+                skill_proficiencies TEXT NOT NULL,
+                notes TEXT
+            );
+            INSERT INTO characters VALUES (1, 'Ada', 'Fighter 1 / Wizard 4', 'Elf', 5, 20, 30, 12, 30,
+                10, 14, 12, 16, 10, 8, '[2,1,0,0,0,0,0,0,0]', '["Rope","Torch",""]', '["arcana"]', NULL);
+            "#,
+        )
+        .unwrap();
+    }
+
+    #[test]
+    fn migrates_an_old_database() {
+        let file = TempDb::new("migrate");
+        create_old_schema(file.path());
+        let db = Db::open_or_create(file.path()).unwrap();
+
+        let characters = db.list_characters().unwrap();
+        let [c] = &characters[..] else { panic!("expected one character") };
+        assert_eq!(c.name, "Ada");
+        assert_eq!(c.spell_slots, [2, 1, 0, 0, 0, 0, 0, 0, 0]);
+        assert_eq!(c.spell_slots_max, c.spell_slots);
+        assert!(c.spell_slots_manual);
+        assert_eq!(c.skill_proficiencies, ["arcana"]);
+        assert!(c.hit_dice_spent.is_empty());
+
+        let items = db.list_items(1).unwrap();
+        let names: Vec<&str> = items.iter().map(|i| i.name.as_str()).collect();
+        assert_eq!(names, ["Rope", "Torch"]);
+        assert!(items.iter().all(|i| i.quantity == 1));
+        assert!(!db.has_column("characters", "inventory").unwrap());
+
+        // Opening again finds nothing left to migrate.
+        drop(db);
+        let db = Db::open_or_create(file.path()).unwrap();
+        assert_eq!(db.list_items(1).unwrap().len(), 2);
+    }
+}
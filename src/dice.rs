@@ -1,27 +1,1049 @@
-use rand::Rng;
-
-pub fn roll(dice: &str, modifier: i32) -> (i32, Vec<i32>) {
-    // Very simple parser: NdM or dM where N default 1
-    // Supports advantage/disadvantage with "2d20kh1" or "2d20kl1" (keep high/low 1)
-    // For simplicity here: support NdM only
-    let (count, sides) = parse_dice(dice).unwrap_or((1, 20));
-    let mut rng = rand::thread_rng();
-    let mut rolls = Vec::with_capacity(count as usize);
-    let mut total = 0;
-    for _ in 0..count {
-        let r = rng.gen_range(1..=sides);
-        rolls.push(r);
-        total += r;
-    }
-    (total + modifier, rolls)
-}
-
-pub fn parse_dice(spec: &str) -> Option<(i32, i32)> {
-    let s = spec.trim().to_lowercase();
-    let parts: Vec<&str> = s.split('d').collect();
-    if parts.len() != 2 { return None; }
-    let count = if parts[0].is_empty() { 1 } else { parts[0].parse().ok()? };
-    let sides = parts[1].parse().ok()?;
-    if count <= 0 || sides <= 0 { return None; }
-    Some((count, sides))
+use std::fmt;
+
+use rand::{Rng, RngCore, SeedableRng};
+use rand_chacha::ChaCha8Rng;
+use serde::{Deserialize, Serialize};
+use thiserror::Error;
+
+/// Upper bound on the number of dice in a single term (e.g. `100d6`).
+pub const MAX_DICE: i32 = 100;
+/// Upper bound on the number of sides of a die.
+pub const MAX_SIDES: i32 = 1000;
+/// Upper bound on how many times a single die can explode or be rerolled.
+pub const MAX_CHAIN: usize = 20;
+
+#[derive(Debug, Clone, PartialEq, Eq, Error)]
+pub enum DiceError {
+    #[error("empty dice expression")]
+    Empty,
+    #[error("unexpected '{0}' at position {1}")]
+    UnexpectedChar(char, usize),
+    #[error("unexpected end of expression")]
+    UnexpectedEnd,
+    #[error("number too large at position {0}")]
+    NumberTooLarge(usize),
+    #[error("dice count must be between 1 and {MAX_DICE}")]
+    BadCount,
+    #[error("dice sides must be between 1 and {MAX_SIDES}")]
+    BadSides,
+    #[error("keep/drop count must be between 1 and the number of dice")]
+    BadKeep,
+    #[error("minimum face must be between 1 and the dice sides")]
+    BadMin,
+    #[error("'{0}' would apply to every face of the die")]
+    EveryFace(&'static str),
+    #[error("duplicate dice modifier at position {0}")]
+    DuplicateModifier(usize),
+    #[error("unknown reference '@{0}'")]
+    UnknownReference(String),
+}
+
+/// Which faces a reroll or explosion applies to: `=N` (or bare `N`), `<N`, `>N`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum Compare {
+    Eq(i32),
+    Lt(i32),
+    Gt(i32),
+}
+
+impl Compare {
+    pub fn matches(self, value: i32) -> bool {
+        match self {
+            Compare::Eq(n) => value == n,
+            Compare::Lt(n) => value < n,
+            Compare::Gt(n) => value > n,
+        }
+    }
+
+    fn covers_all(self, sides: i32) -> bool {
+        (1..=sides).all(|v| self.matches(v))
+    }
+}
+
+impl fmt::Display for Compare {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Compare::Eq(n) => write!(f, "{}", n),
+            Compare::Lt(n) => write!(f, "<{}", n),
+            Compare::Gt(n) => write!(f, ">{}", n),
+        }
+    }
+}
+
+/// Reroll modifier: `rN` rerolls until the face no longer matches, `roN` rerolls once.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Reroll {
+    pub on: Compare,
+    pub once: bool,
+}
+
+/// Explosion modifier: `!` adds another die on a matching face, `!!` adds it to the same die.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Explode {
+    pub on: Compare,
+    pub compound: bool,
+}
+
+/// Keep/drop modifier of a dice term (`kh`, `kl`, `dh`, `dl`).
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum Keep {
+    Highest(i32),
+    Lowest(i32),
+    DropHighest(i32),
+    DropLowest(i32),
+}
+
+impl Keep {
+    /// Marks which of `values` survive this modifier. Ties favour the earlier die.
+    fn kept(self, values: &[i32]) -> Vec<bool> {
+        let len = values.len();
+        let mut order: Vec<usize> = (0..len).collect();
+        // Highest first.
+        order.sort_by(|&a, &b| values[b].cmp(&values[a]).then(a.cmp(&b)));
+        let (from_top, n) = match self {
+            Keep::Highest(n) => (true, n as usize),
+            Keep::Lowest(n) => (false, n as usize),
+            Keep::DropHighest(n) => (false, len.saturating_sub(n as usize)),
+            Keep::DropLowest(n) => (true, len.saturating_sub(n as usize)),
+        };
+        if !from_top {
+            order.sort_by(|&a, &b| values[a].cmp(&values[b]).then(a.cmp(&b)));
+        }
+        let mut mask = vec![false; len];
+        for &i in order.iter().take(n) {
+            mask[i] = true;
+        }
+        mask
+    }
+}
+
+impl fmt::Display for Keep {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Keep::Highest(n) => write!(f, "kh{}", n),
+            Keep::Lowest(n) => write!(f, "kl{}", n),
+            Keep::DropHighest(n) => write!(f, "dh{}", n),
+            Keep::DropLowest(n) => write!(f, "dl{}", n),
+        }
+    }
+}
+
+/// A single `NdM` term with its modifiers.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct DiceTerm {
+    pub count: i32,
+    pub sides: i32,
+    /// Faces below this are raised to it (`minN`).
+    pub min: Option<i32>,
+    pub reroll: Option<Reroll>,
+    pub explode: Option<Explode>,
+    pub keep: Option<Keep>,
+}
+
+impl DiceTerm {
+    pub fn new(count: i32, sides: i32) -> Self {
+        Self { count, sides, min: None, reroll: None, explode: None, keep: None }
+    }
+
+    /// Rolls one die of this term, applying reroll, minimum and explosion.
+    /// Returns more than one die when a plain `!` explosion triggers.
+    fn roll_die<R: Rng + ?Sized>(&self, rng: &mut R) -> Vec<Die> {
+        let mut dice = Vec::new();
+        let mut chain = 0;
+        loop {
+            let mut die = Die::new(rng.gen_range(1..=self.sides));
+            if let Some(reroll) = self.reroll {
+                let limit = if reroll.once { 1 } else { MAX_CHAIN };
+                while die.replaced.len() < limit && reroll.on.matches(die.value) {
+                    die.replaced.push(die.value);
+                    die.value = rng.gen_range(1..=self.sides);
+                }
+            }
+            if let Some(min) = self.min
+                && die.value < min
+            {
+                die.replaced.push(die.value);
+                die.value = min;
+            }
+            let Some(explode) = self.explode else {
+                dice.push(die);
+                return dice;
+            };
+            let mut face = die.value;
+            while explode.on.matches(face) && chain < MAX_CHAIN {
+                chain += 1;
+                if !explode.compound {
+                    die.exploded = true;
+                    break;
+                }
+                face = rng.gen_range(1..=self.sides);
+                die.compounded.push(face);
+                die.value += face;
+            }
+            let again = die.exploded;
+            dice.push(die);
+            if !again {
+                return dice;
+            }
+        }
+    }
+}
+
+/// Whether a d20 check is rolled with advantage or disadvantage.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum RollMode {
+    #[default]
+    Normal,
+    Advantage,
+    Disadvantage,
+}
+
+impl RollMode {
+    /// Short label for status lines, empty for a normal roll.
+    pub fn label(self) -> &'static str {
+        match self {
+            RollMode::Normal => "",
+            RollMode::Advantage => " (adv)",
+            RollMode::Disadvantage => " (dis)",
+        }
+    }
+}
+
+/// Splits a trailing `adv`/`dis` (or `advantage`/`disadvantage`) off roll input.
+pub fn split_advantage(input: &str) -> (&str, RollMode) {
+    let input = input.trim();
+    for (suffix, adv) in [
+        ("advantage", RollMode::Advantage),
+        ("adv", RollMode::Advantage),
+        ("disadvantage", RollMode::Disadvantage),
+        ("dis", RollMode::Disadvantage),
+    ] {
+        if let Some(rest) = input.strip_suffix(suffix)
+            && rest.ends_with(char::is_whitespace)
+        {
+            return (rest.trim_end(), adv);
+        }
+    }
+    (input, RollMode::Normal)
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum BinOp {
+    Add,
+    Sub,
+    Mul,
+}
+
+impl BinOp {
+    fn precedence(self) -> u8 {
+        match self {
+            BinOp::Add | BinOp::Sub => 1,
+            BinOp::Mul => 2,
+        }
+    }
+
+    fn symbol(self) -> &'static str {
+        match self {
+            BinOp::Add => "+",
+            BinOp::Sub => "-",
+            BinOp::Mul => "*",
+        }
+    }
+}
+
+/// Parsed dice expression, e.g. `2d6+3` or `(1d8+1d6)*2`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Expr {
+    Num(i32),
+    Dice(DiceTerm),
+    Neg(Box<Expr>),
+    Binary(BinOp, Box<Expr>, Box<Expr>),
+}
+
+/// One rolled die; dropped dice don't count towards the total.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Die {
+    /// Counted value, including any compounded explosions.
+    pub value: i32,
+    pub kept: bool,
+    /// Faces thrown away by rerolls or raised by a minimum, oldest first.
+    pub replaced: Vec<i32>,
+    /// Extra faces added to this die by `!!`.
+    pub compounded: Vec<i32>,
+    /// This die exploded with `!` and the next die in the group came from it.
+    pub exploded: bool,
+}
+
+impl Die {
+    fn new(value: i32) -> Self {
+        Self { value, kept: true, replaced: Vec::new(), compounded: Vec::new(), exploded: false }
+    }
+
+    /// The face shown on the die, before compounded explosions are added.
+    pub fn face(&self) -> i32 {
+        self.value - self.compounded.iter().sum::<i32>()
+    }
+}
+
+impl fmt::Display for Die {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let mut s = String::new();
+        for r in &self.replaced {
+            s.push_str(&format!("{}→", r));
+        }
+        s.push_str(&self.face().to_string());
+        for c in &self.compounded {
+            s.push_str(&format!("+{}", c));
+        }
+        if self.exploded || !self.compounded.is_empty() { s.push('!'); }
+        if self.kept { f.write_str(&s) } else { write!(f, "~{}~", s) }
+    }
+}
+
+/// The dice rolled for one term of an expression.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct DiceGroup {
+    pub term: DiceTerm,
+    pub rolls: Vec<Die>,
+    pub subtotal: i32,
+}
+
+/// Outcome of rolling an expression.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RollResult {
+    pub expr: Expr,
+    pub groups: Vec<DiceGroup>,
+    pub total: i32,
+}
+
+/// A natural 20 or natural 1 on a d20.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Natural {
+    Crit,
+    Fumble,
+}
+
+impl Natural {
+    /// Tag appended to status lines.
+    pub fn tag(self) -> &'static str {
+        match self {
+            Natural::Crit => " 💥 CRIT!",
+            Natural::Fumble => " 💀 FUMBLE!",
+        }
+    }
+}
+
+/// Natural 20/1 of the first kept d20 in `groups`. A face raised by `min` isn't natural.
+pub fn natural(groups: &[DiceGroup]) -> Option<Natural> {
+    let die = groups.iter().filter(|g| g.term.sides == 20).flat_map(|g| &g.rolls).find(|d| d.kept)?;
+    match die.face() {
+        20 => Some(Natural::Crit),
+        1 => Some(Natural::Fumble),
+        _ => None,
+    }
+}
+
+/// How damage dice are boosted on a critical hit.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum CritRule {
+    /// Roll every damage die twice (the SRD rule).
+    #[default]
+    DoubleDice,
+    /// Take the maximum of the damage dice and roll them once more.
+    MaxPlusRoll,
+}
+
+impl CritRule {
+    pub fn label(self) -> &'static str {
+        match self {
+            CritRule::DoubleDice => "double dice",
+            CritRule::MaxPlusRoll => "max + roll",
+        }
+    }
+
+    pub fn toggle(self) -> Self {
+        match self {
+            CritRule::DoubleDice => CritRule::MaxPlusRoll,
+            CritRule::MaxPlusRoll => CritRule::DoubleDice,
+        }
+    }
+
+    /// Key stored in the settings table.
+    pub fn key(self) -> &'static str {
+        match self {
+            CritRule::DoubleDice => "double",
+            CritRule::MaxPlusRoll => "max",
+        }
+    }
+
+    pub fn from_key(key: &str) -> Self {
+        if key == "max" { CritRule::MaxPlusRoll } else { CritRule::DoubleDice }
+    }
+}
+
+impl RollResult {
+    pub fn natural(&self) -> Option<Natural> {
+        natural(&self.groups)
+    }
+
+    /// Flat bonus added on top of the dice, e.g. `+5` in `1d20+5`.
+    pub fn modifier(&self) -> i32 {
+        self.expr.flat_modifier()
+    }
+
+    /// Human readable breakdown, e.g. `2d20kh1[17, ~3~] + 3`.
+    ///
+    /// Dropped dice are struck with `~`, rerolls show as `1→4` and explosions as `6!`.
+    pub fn breakdown(&self) -> String {
+        let mut groups = self.groups.iter();
+        let mut out = String::new();
+        write_expr(&self.expr, &mut out, &mut |_| {
+            let g = groups.next().expect("one group per dice term");
+            let rolls = g.rolls.iter().map(|r| r.to_string()).collect::<Vec<_>>().join(", ");
+            format!("{}[{}]", g.term, rolls)
+        });
+        out
+    }
+}
+
+impl fmt::Display for RollResult {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} = {}", self.breakdown(), self.total)
+    }
+}
+
+impl fmt::Display for DiceTerm {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}d{}", self.count, self.sides)?;
+        if let Some(min) = self.min { write!(f, "min{}", min)?; }
+        if let Some(r) = self.reroll { write!(f, "{}{}", if r.once { "ro" } else { "r" }, r.on)?; }
+        if let Some(e) = self.explode {
+            f.write_str(if e.compound { "!!" } else { "!" })?;
+            if e.on != Compare::Eq(self.sides) { write!(f, "{}", e.on)?; }
+        }
+        if let Some(keep) = self.keep { write!(f, "{}", keep)?; }
+        Ok(())
+    }
+}
+
+impl fmt::Display for Expr {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let mut out = String::new();
+        write_expr(self, &mut out, &mut |t| t.to_string());
+        f.write_str(&out)
+    }
+}
+
+impl Expr {
+    fn precedence(&self) -> u8 {
+        match self {
+            Expr::Binary(op, _, _) => op.precedence(),
+            _ => 3,
+        }
+    }
+
+    /// Sum of the constants added or subtracted at the top level of the expression.
+    fn flat_modifier(&self) -> i32 {
+        match self {
+            Expr::Num(n) => *n,
+            Expr::Dice(_) | Expr::Binary(BinOp::Mul, _, _) => 0,
+            Expr::Neg(inner) => inner.flat_modifier().saturating_neg(),
+            Expr::Binary(BinOp::Add, lhs, rhs) => lhs.flat_modifier().saturating_add(rhs.flat_modifier()),
+            Expr::Binary(BinOp::Sub, lhs, rhs) => lhs.flat_modifier().saturating_sub(rhs.flat_modifier()),
+        }
+    }
+
+    /// Critical damage: boosts every dice term according to `rule`; flat modifiers are unchanged.
+    pub fn critical(self, rule: CritRule) -> Expr {
+        match self {
+            Expr::Dice(term) => match rule {
+                CritRule::DoubleDice => {
+                    let keep = term.keep.map(|k| match k {
+                        Keep::Highest(n) => Keep::Highest(n * 2),
+                        Keep::Lowest(n) => Keep::Lowest(n * 2),
+                        Keep::DropHighest(n) => Keep::DropHighest(n * 2),
+                        Keep::DropLowest(n) => Keep::DropLowest(n * 2),
+                    });
+                    Expr::Dice(DiceTerm { count: (term.count * 2).min(MAX_DICE), keep, ..term })
+                }
+                CritRule::MaxPlusRoll => {
+                    let counted = match term.keep {
+                        Some(Keep::Highest(n) | Keep::Lowest(n)) => n,
+                        Some(Keep::DropHighest(n) | Keep::DropLowest(n)) => term.count - n,
+                        None => term.count,
+                    };
+                    Expr::Binary(BinOp::Add, Box::new(Expr::Num(counted * term.sides)), Box::new(Expr::Dice(term)))
+                }
+            },
+            Expr::Neg(inner) => Expr::Neg(Box::new(inner.critical(rule))),
+            Expr::Binary(op, lhs, rhs) => Expr::Binary(op, Box::new(lhs.critical(rule)), Box::new(rhs.critical(rule))),
+            other => other,
+        }
+    }
+
+    /// Whether the expression rolls a plain `1d20`, i.e. is a d20 test that
+    /// [`Expr::with_mode`] can roll with advantage or disadvantage.
+    pub fn has_d20(&self) -> bool {
+        match self {
+            Expr::Num(_) => false,
+            Expr::Dice(term) => *term == DiceTerm::new(1, 20),
+            Expr::Neg(inner) => inner.has_d20(),
+            Expr::Binary(_, lhs, rhs) => lhs.has_d20() || rhs.has_d20(),
+        }
+    }
+
+    /// Rolls every plain `1d20` in the expression with advantage or disadvantage.
+    pub fn with_mode(self, mode: RollMode) -> Expr {
+        match self {
+            Expr::Dice(term) if term == DiceTerm::new(1, 20) => Expr::Dice(d20_term(mode)),
+            Expr::Neg(inner) => Expr::Neg(Box::new(inner.with_mode(mode))),
+            Expr::Binary(op, lhs, rhs) => Expr::Binary(op, Box::new(lhs.with_mode(mode)), Box::new(rhs.with_mode(mode))),
+            other => other,
+        }
+    }
+
+    /// Rolls every dice term with `rng` and evaluates the expression.
+    pub fn roll<R: Rng + ?Sized>(&self, rng: &mut R) -> RollResult {
+        let mut groups = Vec::new();
+        let total = self.eval(rng, &mut groups);
+        RollResult { expr: self.clone(), groups, total }
+    }
+
+    fn eval<R: Rng + ?Sized>(&self, rng: &mut R, groups: &mut Vec<DiceGroup>) -> i32 {
+        match self {
+            Expr::Num(n) => *n,
+            Expr::Dice(term) => {
+                let mut rolls: Vec<Die> = (0..term.count).flat_map(|_| term.roll_die(rng)).collect();
+                if let Some(keep) = term.keep {
+                    let values: Vec<i32> = rolls.iter().map(|d| d.value).collect();
+                    for (die, kept) in rolls.iter_mut().zip(keep.kept(&values)) {
+                        die.kept = kept;
+                    }
+                }
+                let subtotal = rolls.iter().filter(|d| d.kept).map(|d| d.value).sum();
+                groups.push(DiceGroup { term: *term, rolls, subtotal });
+                subtotal
+            }
+            Expr::Neg(inner) => inner.eval(rng, groups).saturating_neg(),
+            Expr::Binary(op, lhs, rhs) => {
+                let l = lhs.eval(rng, groups);
+                let r = rhs.eval(rng, groups);
+                match op {
+                    BinOp::Add => l.saturating_add(r),
+                    BinOp::Sub => l.saturating_sub(r),
+                    BinOp::Mul => l.saturating_mul(r),
+                }
+            }
+        }
+    }
+}
+
+/// Writes `expr` with the minimum parentheses, rendering dice terms with `dice`.
+fn write_expr(expr: &Expr, out: &mut String, dice: &mut dyn FnMut(&DiceTerm) -> String) {
+    match expr {
+        Expr::Num(n) => out.push_str(&n.to_string()),
+        Expr::Dice(term) => out.push_str(&dice(term)),
+        Expr::Neg(inner) => {
+            out.push('-');
+            write_operand(inner, inner.precedence() < 3, out, dice);
+        }
+        Expr::Binary(op, lhs, rhs) => {
+            let prec = op.precedence();
+            write_operand(lhs, lhs.precedence() < prec, out, dice);
+            out.push_str(&format!(" {} ", op.symbol()));
+            let paren = rhs.precedence() < prec || (rhs.precedence() == prec && *op == BinOp::Sub);
+            write_operand(rhs, paren, out, dice);
+        }
+    }
+}
+
+fn write_operand(expr: &Expr, paren: bool, out: &mut String, dice: &mut dyn FnMut(&DiceTerm) -> String) {
+    if paren { out.push('('); }
+    write_expr(expr, out, dice);
+    if paren { out.push(')'); }
+}
+
+/// Parses a dice expression.
+///
+/// Grammar (whitespace is ignored, case-insensitive):
+/// ```text
+/// expr   := term (('+' | '-') term)*
+/// term   := unary ('*' unary)*
+/// unary  := '-' unary | '+' unary | atom
+/// atom   := NUM | dice | '@' NAME | 'adv' | 'dis' | '(' expr ')'
+/// dice   := NUM? 'd' (NUM | '%') mod*
+/// mod    := 'min' NUM | ('r' | 'ro') cmp | ('!' | '!!') cmp? | keep
+/// keep   := ('kh' | 'kl' | 'k' | 'dh' | 'dl') NUM?
+/// cmp    := ('<' | '>' | '=')? NUM
+/// ```
+///
+/// `adv` and `dis` are shorthands for `2d20kh1` and `2d20kl1`. Explosions
+/// default to the highest face, and each die explodes or rerolls at most
+/// [`MAX_CHAIN`] times. References like `@str` are rejected; see [`parse_with`].
+pub fn parse(input: &str) -> Result<Expr, DiceError> {
+    parse_with(input, &|_| None)
+}
+
+/// Parses a dice expression, replacing `@name` references with the value `refs` gives them.
+pub fn parse_with(input: &str, refs: &dyn Fn(&str) -> Option<i32>) -> Result<Expr, DiceError> {
+    let mut p = Parser { chars: input.to_lowercase().chars().collect(), pos: 0, refs };
+    if p.peek().is_none() { return Err(DiceError::Empty); }
+    let expr = p.expr()?;
+    match p.peek() {
+        None => Ok(expr),
+        Some(c) => Err(DiceError::UnexpectedChar(c, p.pos)),
+    }
+}
+
+/// Rolls dice expressions with an owned random number generator.
+///
+/// Seeded rollers use ChaCha8, whose output is stable across platforms and
+/// releases, so the same seed always replays the same sequence of rolls.
+pub struct DiceRoller {
+    rng: Box<dyn RngCore>,
+    seed: Option<u64>,
+}
+
+impl DiceRoller {
+    /// Wraps any generator, e.g. a mock or a pre-seeded RNG.
+    pub fn new(rng: impl RngCore + 'static) -> Self {
+        Self { rng: Box::new(rng), seed: None }
+    }
+
+    /// A roller seeded from OS entropy, for normal play.
+    pub fn from_entropy() -> Self {
+        Self::new(ChaCha8Rng::from_entropy())
+    }
+
+    /// A deterministic roller: the same seed produces the same rolls.
+    pub fn seeded(seed: u64) -> Self {
+        Self { rng: Box::new(ChaCha8Rng::seed_from_u64(seed)), seed: Some(seed) }
+    }
+
+    /// The seed of a deterministic roller.
+    pub fn seed(&self) -> Option<u64> {
+        self.seed
+    }
+
+    pub fn roll_expr(&mut self, expr: &Expr) -> RollResult {
+        expr.roll(&mut self.rng)
+    }
+}
+
+struct Parser<'a> {
+    chars: Vec<char>,
+    pos: usize,
+    refs: &'a dyn Fn(&str) -> Option<i32>,
+}
+
+impl Parser<'_> {
+    fn skip_ws(&mut self) {
+        while self.chars.get(self.pos).is_some_and(|c| c.is_whitespace()) {
+            self.pos += 1;
+        }
+    }
+
+    fn peek(&mut self) -> Option<char> {
+        self.skip_ws();
+        self.chars.get(self.pos).copied()
+    }
+
+    fn eat(&mut self, c: char) -> bool {
+        if self.peek() == Some(c) {
+            self.pos += 1;
+            true
+        } else {
+            false
+        }
+    }
+
+    /// Consumes `word` if the input continues with it, without skipping inner whitespace.
+    fn eat_word(&mut self, word: &str) -> bool {
+        self.skip_ws();
+        let len = word.chars().count();
+        let matches = self.chars.len() >= self.pos + len
+            && self.chars[self.pos..self.pos + len].iter().copied().eq(word.chars());
+        if matches { self.pos += len; }
+        matches
+    }
+
+    fn expr(&mut self) -> Result<Expr, DiceError> {
+        let mut lhs = self.term()?;
+        loop {
+            let op = match self.peek() {
+                Some('+') => BinOp::Add,
+                Some('-') => BinOp::Sub,
+                _ => return Ok(lhs),
+            };
+            self.pos += 1;
+            let rhs = self.term()?;
+            lhs = Expr::Binary(op, Box::new(lhs), Box::new(rhs));
+        }
+    }
+
+    fn term(&mut self) -> Result<Expr, DiceError> {
+        let mut lhs = self.unary()?;
+        while self.eat('*') {
+            let rhs = self.unary()?;
+            lhs = Expr::Binary(BinOp::Mul, Box::new(lhs), Box::new(rhs));
+        }
+        Ok(lhs)
+    }
+
+    fn unary(&mut self) -> Result<Expr, DiceError> {
+        if self.eat('-') {
+            return Ok(Expr::Neg(Box::new(self.unary()?)));
+        }
+        if self.eat('+') {
+            return self.unary();
+        }
+        self.atom()
+    }
+
+    fn atom(&mut self) -> Result<Expr, DiceError> {
+        match self.peek() {
+            None => Err(DiceError::UnexpectedEnd),
+            Some('(') => {
+                self.pos += 1;
+                let inner = self.expr()?;
+                if self.eat(')') {
+                    Ok(inner)
+                } else {
+                    match self.peek() {
+                        None => Err(DiceError::UnexpectedEnd),
+                        Some(c) => Err(DiceError::UnexpectedChar(c, self.pos)),
+                    }
+                }
+            }
+            Some('@') => {
+                self.pos += 1;
+                let start = self.pos;
+                while self.chars.get(self.pos).is_some_and(|c| c.is_alphanumeric() || *c == '_') {
+                    self.pos += 1;
+                }
+                let name: String = self.chars[start..self.pos].iter().collect();
+                (self.refs)(&name).map(Expr::Num).ok_or(DiceError::UnknownReference(name))
+            }
+            Some(_) if self.eat_word("adv") => Ok(Expr::Dice(d20_term(RollMode::Advantage))),
+            Some(_) if self.eat_word("dis") => Ok(Expr::Dice(d20_term(RollMode::Disadvantage))),
+            Some('d') => {
+                self.pos += 1;
+                self.dice(1)
+            }
+            Some(c) if c.is_ascii_digit() => {
+                let n = self.number()?;
+                if self.eat('d') { self.dice(n) } else { Ok(Expr::Num(n)) }
+            }
+            Some(c) => Err(DiceError::UnexpectedChar(c, self.pos)),
+        }
+    }
+
+    /// Parses the sides of a dice term after the `d`.
+    fn dice(&mut self, count: i32) -> Result<Expr, DiceError> {
+        let sides = match self.peek() {
+            Some('%') => {
+                self.pos += 1;
+                100
+            }
+            Some(c) if c.is_ascii_digit() => self.number()?,
+            Some(c) => return Err(DiceError::UnexpectedChar(c, self.pos)),
+            None => return Err(DiceError::UnexpectedEnd),
+        };
+        if !(1..=MAX_DICE).contains(&count) { return Err(DiceError::BadCount); }
+        if !(1..=MAX_SIDES).contains(&sides) { return Err(DiceError::BadSides); }
+        let mut term = DiceTerm::new(count, sides);
+        loop {
+            let start = self.pos;
+            let duplicate = if self.eat_word("min") {
+                let min = self.number()?;
+                term.min.replace(min).is_some()
+            } else if self.eat_word("ro") {
+                let on = self.compare()?;
+                term.reroll.replace(Reroll { on, once: true }).is_some()
+            } else if self.eat_word("r") {
+                let on = self.compare()?;
+                term.reroll.replace(Reroll { on, once: false }).is_some()
+            } else if self.eat('!') {
+                let compound = self.eat('!');
+                let on = if self.peek().is_some_and(|c| "<>=".contains(c) || c.is_ascii_digit()) {
+                    self.compare()?
+                } else {
+                    Compare::Eq(sides)
+                };
+                term.explode.replace(Explode { on, compound }).is_some()
+            } else if let Some(keep) = self.keep()? {
+                term.keep.replace(keep).is_some()
+            } else {
+                break;
+            };
+            if duplicate { return Err(DiceError::DuplicateModifier(start)); }
+        }
+        if let Some(Keep::Highest(n) | Keep::Lowest(n) | Keep::DropHighest(n) | Keep::DropLowest(n)) = term.keep
+            && !(1..=count).contains(&n)
+        {
+            return Err(DiceError::BadKeep);
+        }
+        if term.min.is_some_and(|m| !(1..=sides).contains(&m)) { return Err(DiceError::BadMin); }
+        if term.reroll.is_some_and(|r| !r.once && r.on.covers_all(sides)) { return Err(DiceError::EveryFace("reroll")); }
+        if term.explode.is_some_and(|e| e.on.covers_all(sides)) { return Err(DiceError::EveryFace("explode")); }
+        Ok(Expr::Dice(term))
+    }
+
+    fn compare(&mut self) -> Result<Compare, DiceError> {
+        let make: fn(i32) -> Compare = if self.eat('<') {
+            Compare::Lt
+        } else if self.eat('>') {
+            Compare::Gt
+        } else {
+            self.eat('=');
+            Compare::Eq
+        };
+        match self.peek() {
+            Some(c) if c.is_ascii_digit() => Ok(make(self.number()?)),
+            Some(c) => Err(DiceError::UnexpectedChar(c, self.pos)),
+            None => Err(DiceError::UnexpectedEnd),
+        }
+    }
+
+    fn keep(&mut self) -> Result<Option<Keep>, DiceError> {
+        let make: fn(i32) -> Keep = if self.eat_word("kh") {
+            Keep::Highest
+        } else if self.eat_word("kl") {
+            Keep::Lowest
+        } else if self.eat_word("dh") {
+            Keep::DropHighest
+        } else if self.eat_word("dl") {
+            Keep::DropLowest
+        } else if self.eat_word("k") {
+            Keep::Highest
+        } else {
+            return Ok(None);
+        };
+        let n = if self.peek().is_some_and(|c| c.is_ascii_digit()) { self.number()? } else { 1 };
+        Ok(Some(make(n)))
+    }
+
+    fn number(&mut self) -> Result<i32, DiceError> {
+        self.skip_ws();
+        let start = self.pos;
+        while self.chars.get(self.pos).is_some_and(|c| c.is_ascii_digit()) {
+            self.pos += 1;
+        }
+        if start == self.pos {
+            return Err(match self.peek() {
+                Some(c) => DiceError::UnexpectedChar(c, self.pos),
+                None => DiceError::UnexpectedEnd,
+            });
+        }
+        let digits: String = self.chars[start..self.pos].iter().collect();
+        digits.parse().map_err(|_| DiceError::NumberTooLarge(start))
+    }
+}
+
+/// The d20 of a check: `1d20`, or `2d20kh1`/`2d20kl1` with advantage/disadvantage.
+fn d20_term(mode: RollMode) -> DiceTerm {
+    match mode {
+        RollMode::Normal => DiceTerm::new(1, 20),
+        RollMode::Advantage => DiceTerm { keep: Some(Keep::Highest(1)), ..DiceTerm::new(2, 20) },
+        RollMode::Disadvantage => DiceTerm { keep: Some(Keep::Lowest(1)), ..DiceTerm::new(2, 20) },
+    }
+}
+
+/// `1d20 + modifier`, the expression behind every check and save.
+pub fn d20_check(modifier: i32, mode: RollMode) -> Expr {
+    plus_modifier(Expr::Dice(d20_term(mode)), modifier)
+}
+
+/// `expr + modifier`, written as a subtraction for negative modifiers.
+pub fn plus_modifier(expr: Expr, modifier: i32) -> Expr {
+    match modifier {
+        0 => expr,
+        m if m < 0 => Expr::Binary(BinOp::Sub, Box::new(expr), Box::new(Expr::Num(-m))),
+        m => Expr::Binary(BinOp::Add, Box::new(expr), Box::new(Expr::Num(m))),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    impl DiceRoller {
+        /// Parses and rolls `input`.
+        pub(crate) fn roll(&mut self, input: &str) -> Result<RollResult, DiceError> {
+            Ok(self.roll_expr(&parse(input)?))
+        }
+    }
+
+    fn totals(roller: &mut DiceRoller, input: &str, n: usize) -> Vec<i32> {
+        (0..n).map(|_| roller.roll(input).unwrap().total).collect()
+    }
+
+    #[test]
+    fn rolls_stay_within_bounds() {
+        let mut roller = DiceRoller::seeded(3);
+        for total in totals(&mut roller, "3d6", 500) {
+            assert!((3..=18).contains(&total));
+        }
+        for total in totals(&mut roller, "(1d4+1)*2-1d6", 500) {
+            assert!((-2..=9).contains(&total));
+        }
+    }
+
+    #[test]
+    fn parses_expressions() {
+        for (input, shown) in [
+            ("d20", "1d20"),
+            ("2d6+3", "2d6 + 3"),
+            ("(1d8+1d6)*2", "(1d8 + 1d6) * 2"),
+            ("-1d4+2", "-1d4 + 2"),
+            ("d%", "1d100"),
+        ] {
+            assert_eq!(parse(input).unwrap().to_string(), shown, "{}", input);
+        }
+    }
+
+    #[test]
+    fn rejects_bad_expressions() {
+        for (input, err) in [
+            ("", DiceError::Empty),
+            ("2d", DiceError::UnexpectedEnd),
+            ("2d6+", DiceError::UnexpectedEnd),
+            ("0d6", DiceError::BadCount),
+            ("1001d6", DiceError::BadCount),
+            ("1d0", DiceError::BadSides),
+        ] {
+            assert_eq!(parse(input), Err(err), "{}", input);
+        }
+    }
+
+    #[test]
+    fn keep_counts_only_the_kept_dice() {
+        let mut roller = DiceRoller::seeded(11);
+        for _ in 0..100 {
+            let res = roller.roll("4d6kh3").unwrap();
+            let rolls = &res.groups[0].rolls;
+            let mut faces: Vec<i32> = rolls.iter().map(|d| d.value).collect();
+            faces.sort_unstable();
+            assert_eq!(rolls.iter().filter(|d| d.kept).count(), 3);
+            assert_eq!(res.total, faces[1..].iter().sum::<i32>());
+        }
+        for (input, shown) in [("2d20kl1", "2d20kl1"), ("4d6dl1", "4d6dl1")] {
+            assert_eq!(parse(input).unwrap().to_string(), shown);
+        }
+        assert_eq!(parse("1d6kh2"), Err(DiceError::BadKeep));
+        assert_eq!(parse("4d6kh3kh2"), Err(DiceError::DuplicateModifier(6)));
+    }
+
+    #[test]
+    fn advantage_rolls_two_d20_and_keeps_one() {
+        assert_eq!(split_advantage("stealth adv"), ("stealth", RollMode::Advantage));
+        assert_eq!(split_advantage("1d20+3 dis"), ("1d20+3", RollMode::Disadvantage));
+        assert_eq!(split_advantage("advice"), ("advice", RollMode::Normal));
+        assert_eq!(d20_check(3, RollMode::Advantage).to_string(), "2d20kh1 + 3");
+        assert_eq!(d20_check(-1, RollMode::Disadvantage).to_string(), "2d20kl1 - 1");
+        assert_eq!(parse("1d20+2").unwrap().with_mode(RollMode::Advantage).to_string(), "2d20kh1 + 2");
+    }
+
+    #[test]
+    fn parses_rerolls_explosions_and_minimums() {
+        for (input, shown) in [
+            ("4d6!", "4d6!"),
+            ("1d6!!", "1d6!!"),
+            ("2d6!>5", "2d6!>5"),
+            ("1d8r1", "1d8r1"),
+            ("2d6ro<3", "2d6ro<3"),
+            ("1d20min10", "1d20min10"),
+        ] {
+            assert_eq!(parse(input).unwrap().to_string(), shown, "{}", input);
+        }
+        for (input, err) in [
+            ("1d6r<7", DiceError::EveryFace("reroll")),
+            ("1d6!>0", DiceError::EveryFace("explode")),
+            ("1d6min0", DiceError::BadMin),
+            ("1d6min10", DiceError::BadMin),
+            ("1d6min", DiceError::UnexpectedEnd),
+            ("1d6minx", DiceError::UnexpectedChar('x', 6)),
+            ("1d6min99999999999", DiceError::NumberTooLarge(6)),
+        ] {
+            assert_eq!(parse(input), Err(err), "{}", input);
+        }
+    }
+
+    #[test]
+    fn rerolls_and_minimums_stay_within_bounds() {
+        let mut roller = DiceRoller::seeded(3);
+        for total in totals(&mut roller, "2d6min3-1", 500) {
+            assert!((5..=11).contains(&total));
+        }
+        for _ in 0..200 {
+            let res = roller.roll("1d6ro1").unwrap();
+            assert!(res.groups[0].rolls[0].replaced.len() <= 1);
+        }
+        for _ in 0..200 {
+            let res = roller.roll("1d6!").unwrap();
+            assert!(res.total >= 1);
+            assert_eq!(res.total, res.groups[0].rolls.iter().map(|d| d.value).sum::<i32>());
+        }
+    }
+
+    #[test]
+    fn seeded_rollers_replay_the_same_rolls() {
+        let mut a = DiceRoller::seeded(42);
+        let mut b = DiceRoller::seeded(42);
+        assert_eq!(a.seed(), Some(42));
+        for input in ["1d20+5", "4d6kh3", "2d6!+1d8r1", "8d6min2"] {
+            assert_eq!(a.roll(input).unwrap(), b.roll(input).unwrap());
+        }
+        assert_ne!(totals(&mut DiceRoller::seeded(1), "1d20", 20), totals(&mut DiceRoller::seeded(2), "1d20", 20));
+    }
+
+    #[test]
+    fn seeded_rolls_are_stable_across_releases() {
+        assert_eq!(totals(&mut DiceRoller::seeded(7), "1d20", 10), [4, 4, 6, 15, 15, 2, 13, 17, 8, 6]);
+        assert_eq!(DiceRoller::seeded(7).roll("4d6kh3").unwrap().to_string(), "4d6kh3[2, 2, ~2~, 5] = 9");
+    }
+
+    #[test]
+    fn flat_modifiers_saturate_like_totals() {
+        let mut roller = DiceRoller::seeded(1);
+        assert_eq!(roller.roll("1d6+3-1").unwrap().modifier(), 2);
+        assert_eq!(roller.roll("(1d6+2)*2").unwrap().modifier(), 0);
+        let res = roller.roll("2000000000+2000000000").unwrap();
+        assert_eq!((res.total, res.modifier()), (i32::MAX, i32::MAX));
+        let res = roller.roll("-2000000000-2000000000-1d4").unwrap();
+        assert_eq!((res.total, res.modifier()), (i32::MIN, i32::MIN));
+    }
+
+    #[test]
+    fn resolves_references() {
+        let refs = |name: &str| (name == "str").then_some(3);
+        assert_eq!(parse_with("1d20+@str", &refs).unwrap().to_string(), "1d20 + 3");
+        assert_eq!(parse("1d20+@str"), Err(DiceError::UnknownReference(String::from("str"))));
+    }
+
+    #[test]
+    fn flags_natural_twenties_and_ones_on_the_kept_d20() {
+        let mut roller = DiceRoller::seeded(9);
+        let mut seen = Vec::new();
+        for _ in 0..200 {
+            let res = roller.roll("2d20kh1+5").unwrap();
+            let kept = res.groups[0].rolls.iter().find(|d| d.kept).unwrap().face();
+            let expected = match kept { 20 => Some(Natural::Crit), 1 => Some(Natural::Fumble), _ => None };
+            assert_eq!(res.natural(), expected);
+            seen.push(expected);
+        }
+        assert!(seen.contains(&Some(Natural::Crit)) && seen.contains(&Some(Natural::Fumble)));
+        assert!((0..50).all(|_| roller.roll("1d6+1d8").unwrap().natural().is_none()));
+    }
+
+    #[test]
+    fn critical_hits_boost_only_the_dice() {
+        let crit = |input: &str, rule| parse(input).unwrap().critical(rule).to_string();
+        assert_eq!(crit("1d8+3", CritRule::DoubleDice), "2d8 + 3");
+        assert_eq!(crit("2d6kh1-1d4", CritRule::DoubleDice), "4d6kh2 - 2d4");
+        assert_eq!(crit("60d6", CritRule::DoubleDice), "100d6");
+        assert_eq!(crit("1d8+3", CritRule::MaxPlusRoll), "8 + 1d8 + 3");
+        assert_eq!(crit("4d6dl1", CritRule::MaxPlusRoll), "18 + 4d6dl1");
+        assert_eq!(crit("5", CritRule::MaxPlusRoll), "5");
+    }
 }
@@ -2,12 +2,33 @@ mod app;
 mod db;
 mod dice;
 mod models;
+mod stats;
 
-use anyhow::Result;
+use anyhow::{bail, Context, Result};
+
+use crate::dice::DiceRoller;
 
 fn main() -> Result<()> {
+    let roller = match parse_seed(std::env::args().skip(1))? {
+        Some(seed) => DiceRoller::seeded(seed),
+        None => DiceRoller::from_entropy(),
+    };
     let db = db::Db::open_or_create("shito.sqlite3")?;
-    let mut app = app::App::new(db)?;
+    let mut app = app::App::new(db, roller)?;
     app.run()?;
     Ok(())
 }
+
+/// Reads `--seed N` (or `--seed=N`) from the command line.
+fn parse_seed(mut args: impl Iterator<Item = String>) -> Result<Option<u64>> {
+    let mut seed = None;
+    while let Some(arg) = args.next() {
+        let value = match arg.strip_prefix("--seed") {
+            Some("") => args.next().context("--seed needs a value")?,
+            Some(rest) if rest.starts_with('=') => rest[1..].to_string(),
+            _ => bail!("unknown argument '{}' (usage: Shito [--seed N])", arg),
+        };
+        seed = Some(value.parse().with_context(|| format!("invalid seed '{}'", value))?);
+    }
+    Ok(seed)
+}
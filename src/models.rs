@@ -1,5 +1,10 @@
+use std::collections::BTreeMap;
+use std::fmt;
+
 use serde::{Deserialize, Serialize};
 
+use crate::dice::{self, DiceError, DiceGroup, DiceTerm, Expr, Natural, RollMode, RollResult};
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct Character {
     pub id: Option<i64>,
@@ -9,6 +14,22 @@ pub struct Character {
     pub level: i32,
     pub hp_current: i32,
     pub hp_max: i32,
+    /// Temporary HP, spent before real HP; doesn't stack
+    pub hp_temp: i32,
+    /// HP maximum lost to effects like a wight's Life Drain
+    pub hp_max_reduction: i32,
+    /// Death saving throws while at 0 HP; reset on healing or stabilizing
+    pub death_successes: i32,
+    pub death_failures: i32,
+    /// Stable at 0 HP: no more death saves until damaged again
+    pub stable: bool,
+    /// Spell being concentrated on, if any
+    pub concentration: Option<String>,
+    pub conditions: Vec<Condition>,
+    /// Exhaustion level 0..=6; 6 is death
+    pub exhaustion: i32,
+    /// Spent hit dice per die size; the totals come from the class levels
+    pub hit_dice_spent: BTreeMap<i32, i32>,
     pub armor_class: i32,
     pub speed: i32,
     pub strength: i32,
@@ -17,13 +38,120 @@ pub struct Character {
     pub intelligence: i32,
     pub wisdom: i32,
     pub charisma: i32,
-    /// Slots for spell level 1..=9, index 0..=8
+    /// Unspent slots for spell level 1..=9, index 0..=8
     pub spell_slots: Vec<i32>,
-    /// Simple inventory list of item names
-    pub inventory: Vec<String>,
+    /// Slots per spell level after a long rest, index 0..=8
+    pub spell_slots_max: Vec<i32>,
+    /// Homebrew: slot maximums are set by hand instead of from class and level
+    pub spell_slots_manual: bool,
+    /// Warlock Pact Magic: unspent and total slots, all of `pact_slot_level`
+    pub pact_slots: i32,
+    pub pact_slots_max: i32,
+    pub pact_slot_level: i32,
     /// Names of proficient skills (e.g., "perception")
     pub skill_proficiencies: Vec<String>,
+    /// Saving throw proficiency per ability, in [`ABILITIES`] order
+    pub save_proficiencies: [bool; 6],
+    /// Damage types taken at half, double or no damage
+    pub resistances: Vec<DamageType>,
+    pub vulnerabilities: Vec<DamageType>,
+    pub immunities: Vec<DamageType>,
     pub notes: Option<String>,
+    pub purse: Purse,
+    /// Named roll shortcuts, e.g. `longsword-hit = 1d20+@str+@prof`.
+    pub macros: Vec<RollMacro>,
+    /// Limited-use class features like Rage or Ki points
+    pub resources: Vec<Resource>,
+}
+
+/// A named dice expression that may reference sheet values like `@str` or `@prof`.
+///
+/// An attack macro is written `attack ; damage`: the damage roll follows the
+/// attack and is boosted on a natural 20.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct RollMacro {
+    pub name: String,
+    pub expression: String,
+    /// Set by a trailing `attack` or `check` tag; untagged rolls ignore conditions
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub kind: Option<RollKind>,
+}
+
+impl RollMacro {
+    /// What the macro rolls for; a macro with a damage part is always an attack.
+    pub fn roll_kind(&self) -> Option<RollKind> {
+        if self.damage().is_some() { Some(RollKind::Attack) } else { self.kind }
+    }
+
+    /// The attack (or only) part of the macro.
+    pub fn attack(&self) -> &str {
+        self.expression.split_once(';').map_or(self.expression.as_str(), |(a, _)| a).trim()
+    }
+
+    /// The damage part of an `attack ; damage` macro.
+    pub fn damage(&self) -> Option<&str> {
+        self.expression.split_once(';').map(|(_, d)| d.trim())
+    }
+}
+
+/// When a [`Resource`] comes back.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Recharge {
+    Short,
+    Long,
+}
+
+impl Recharge {
+    pub fn label(self) -> &'static str {
+        match self {
+            Self::Short => "short rest",
+            Self::Long => "long rest",
+        }
+    }
+}
+
+/// A limited-use class feature, e.g. `rage` with 3 uses per long rest.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Resource {
+    pub name: String,
+    pub current: i32,
+    pub max: i32,
+    pub recharge: Recharge,
+}
+
+/// Hit dice of one size, e.g. `3/5 d10`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct HitDice {
+    pub size: i32,
+    pub remaining: i32,
+    pub total: i32,
+}
+
+impl fmt::Display for HitDice {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}/{} d{}", self.remaining, self.total, self.size)
+    }
+}
+
+/// Where a character stands between conscious and dead.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Vitality {
+    Conscious,
+    Dying,
+    Stable,
+    Dead,
+}
+
+/// Result of a death saving throw.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DeathSave {
+    Success,
+    Failure,
+    /// Natural 20: back up with 1 HP.
+    Revived,
+    Stabilized,
+    Died,
 }
 
 impl Default for Character {
@@ -36,6 +164,15 @@ impl Default for Character {
             level: 1,
             hp_current: 10,
             hp_max: 10,
+            hp_temp: 0,
+            hp_max_reduction: 0,
+            death_successes: 0,
+            death_failures: 0,
+            stable: false,
+            concentration: None,
+            conditions: vec![],
+            exhaustion: 0,
+            hit_dice_spent: BTreeMap::new(),
             armor_class: 10,
             speed: 30,
             strength: 10,
@@ -45,9 +182,20 @@ impl Default for Character {
             wisdom: 10,
             charisma: 10,
             spell_slots: vec![0; 9],
-            inventory: vec![],
+            spell_slots_max: vec![0; 9],
+            spell_slots_manual: false,
+            pact_slots: 0,
+            pact_slots_max: 0,
+            pact_slot_level: 0,
             skill_proficiencies: vec![],
+            save_proficiencies: [false; 6],
+            resistances: vec![],
+            vulnerabilities: vec![],
+            immunities: vec![],
             notes: None,
+            purse: Purse::default(),
+            macros: vec![],
+            resources: vec![],
         }
     }
 }
@@ -69,50 +217,516 @@ impl Character {
         2 + ((self.level - 1) / 4)
     }
 
+    /// Gains a level; a multiclass character gains it in the first listed class.
     pub fn level_up(&mut self) {
+        if let Some(mut classes) = parse_class_levels(&self.class_name) {
+            classes[0].1 += 1;
+            self.class_name = format_class_levels(&classes);
+        }
         self.level += 1;
+        self.sync_spell_slots();
     }
 
-    pub fn change_hp(&mut self, delta: i32) {
-        self.hp_current = (self.hp_current + delta).clamp(0, self.hp_max);
+    /// Sets the class, either `Wizard` or a multiclass like `Paladin 2 / Sorcerer 3`
+    /// whose levels add up to the character level.
+    pub fn set_class(&mut self, class_name: &str) {
+        self.class_name = class_name.trim().to_string();
+        if let Some(classes) = parse_class_levels(&self.class_name) {
+            self.level = classes.iter().map(|(_, l)| l).sum();
+        }
+        // Dropped or lowered classes take their spent dice with them.
+        let totals = self.hit_dice_totals();
+        self.hit_dice_spent.retain(|size, spent| {
+            *spent = (*spent).min(totals.get(size).copied().unwrap_or(0));
+            *spent > 0
+        });
+        self.sync_spell_slots();
     }
 
-    pub fn set_hp(&mut self, new_hp: i32) {
-        self.hp_current = new_hp.clamp(0, self.hp_max);
+    /// Classes with their levels; a single class has all the character levels.
+    pub fn class_levels(&self) -> Vec<(String, i32)> {
+        parse_class_levels(&self.class_name).unwrap_or_else(|| vec![(self.class_name.clone(), self.level)])
+    }
+
+    /// Sets slot maximums from class and level unless they're managed by hand;
+    /// Pact Magic always follows the warlock level. New slots are available right
+    /// away; returns whether anything changed.
+    pub fn sync_spell_slots(&mut self) -> bool {
+        let warlock_level = self.class_levels().iter().filter(|(c, _)| first_word(c) == "warlock").map(|(_, l)| l).sum();
+        let (count, level) = pact_slots_for(warlock_level);
+        let mut changed = count != self.pact_slots_max || level != self.pact_slot_level;
+        self.pact_slots = (self.pact_slots + count - self.pact_slots_max).clamp(0, count);
+        self.pact_slots_max = count;
+        self.pact_slot_level = level;
+        if self.spell_slots_manual {
+            return changed;
+        }
+        let slots = spell_slots_for(spellcaster_level(&self.class_levels()));
+        for (i, max) in slots.into_iter().enumerate() {
+            let delta = max - self.spell_slots_max[i];
+            if delta != 0 {
+                self.spell_slots[i] = (self.spell_slots[i] + delta).clamp(0, max);
+                self.spell_slots_max[i] = max;
+                changed = true;
+            }
+        }
+        changed
+    }
+
+    /// Hit dice per die size: one per level of each class.
+    fn hit_dice_totals(&self) -> BTreeMap<i32, i32> {
+        let mut totals = BTreeMap::new();
+        for (class, level) in self.class_levels() {
+            *totals.entry(hit_die_size(&class)).or_insert(0) += level;
+        }
+        totals
     }
 
-    pub fn add_item(&mut self, item: String) {
-        if !item.trim().is_empty() {
-            self.inventory.push(item);
+    /// Hit dice pools, largest die first.
+    pub fn hit_dice(&self) -> Vec<HitDice> {
+        self.hit_dice_totals()
+            .into_iter()
+            .rev()
+            .map(|(size, total)| {
+                let spent = self.hit_dice_spent.get(&size).copied().unwrap_or(0);
+                HitDice { size, remaining: (total - spent).max(0), total }
+            })
+            .collect()
+    }
+
+    /// Uses up to `count` hit dice of one size, or of the largest sizes first for
+    /// `None`; returns the size of each die spent.
+    pub fn spend_hit_dice(&mut self, count: i32, size: Option<i32>) -> Vec<i32> {
+        let mut spent = Vec::new();
+        for pool in self.hit_dice().into_iter().filter(|h| size.is_none_or(|s| s == h.size)) {
+            let n = (count - spent.len() as i32).clamp(0, pool.remaining);
+            *self.hit_dice_spent.entry(pool.size).or_insert(0) += n;
+            spent.extend(std::iter::repeat_n(pool.size, n as usize));
         }
+        spent
     }
 
-    pub fn remove_item(&mut self, index: usize) {
-        if index < self.inventory.len() {
-            self.inventory.remove(index);
+    /// Regains up to `count` spent hit dice, largest first.
+    fn regain_hit_dice(&mut self, mut count: i32) {
+        for (_, spent) in self.hit_dice_spent.iter_mut().rev() {
+            let n = count.min(*spent);
+            *spent -= n;
+            count -= n;
         }
+        self.hit_dice_spent.retain(|_, spent| *spent > 0);
+    }
+
+    /// One hit die of `size` plus the CON modifier, as rolled when spending it on a short rest.
+    pub fn hit_die_roll(&self, size: i32) -> Expr {
+        dice::plus_modifier(Expr::Dice(DiceTerm::new(1, size)), self.con_mod())
+    }
+
+    /// HP maximum after any reduction, halved from exhaustion level 4.
+    pub fn effective_hp_max(&self) -> i32 {
+        let max = (self.hp_max - self.hp_max_reduction).max(0);
+        if self.exhaustion >= 4 { max / 2 } else { max }
+    }
+
+    /// Speed after encumbrance, conditions and exhaustion.
+    pub fn effective_speed(&self, load: Encumbrance) -> i32 {
+        let stopped = self.conditions.iter().any(|c| c.stops_movement());
+        let speed = match load {
+            Encumbrance::OverCapacity => 5.min(self.speed),
+            _ => (self.speed - load.speed_penalty()).max(0),
+        };
+        if stopped || self.exhaustion >= 5 {
+            0
+        } else if self.exhaustion >= 2 {
+            speed / 2
+        } else {
+            speed
+        }
+    }
+
+    /// Weight that can be carried: 15 × Strength.
+    pub fn carrying_capacity(&self) -> f64 {
+        self.strength as f64 * 15.0
+    }
+
+    /// Weight that can be pushed, dragged or lifted: 30 × Strength.
+    pub fn push_drag_lift(&self) -> f64 {
+        self.strength as f64 * 30.0
     }
 
+    /// Encumbrance from carrying `load` lb; the variant rule adds thresholds
+    /// at 5 × and 10 × Strength below the carrying capacity.
+    pub fn encumbrance(&self, load: f64, variant: bool) -> Encumbrance {
+        let strength = self.strength as f64;
+        if load > self.carrying_capacity() {
+            Encumbrance::OverCapacity
+        } else if variant && load > strength * 10.0 {
+            Encumbrance::HeavilyEncumbered
+        } else if variant && load > strength * 5.0 {
+            Encumbrance::Encumbered
+        } else {
+            Encumbrance::Unencumbered
+        }
+    }
+
+    /// Sets the exhaustion level; level 4 and up halve the HP maximum, 6 kills.
+    pub fn set_exhaustion(&mut self, level: i32) {
+        self.exhaustion = level.clamp(0, 6);
+        self.hp_current = self.hp_current.min(self.effective_hp_max());
+    }
+
+    /// Advantage, disadvantage and automatic failure from conditions and exhaustion.
+    pub fn roll_effects(&self, kind: RollKind) -> RollEffects {
+        let mut fx = RollEffects::default();
+        for &condition in &self.conditions {
+            let name = condition.name();
+            match (condition, kind) {
+                (Condition::Invisible, RollKind::Attack) => fx.advantage.push(name),
+                (Condition::Blinded | Condition::Frightened | Condition::Poisoned | Condition::Prone | Condition::Restrained, RollKind::Attack)
+                | (Condition::Frightened | Condition::Poisoned, RollKind::Check)
+                | (Condition::Restrained, RollKind::Save(Some(1))) => fx.disadvantage.push(name),
+                // STR and DEX saves
+                (Condition::Paralyzed | Condition::Petrified | Condition::Stunned | Condition::Unconscious, RollKind::Save(Some(0 | 1))) => {
+                    fx.auto_fail.push(name)
+                }
+                _ => {}
+            }
+        }
+        let exhausted = match kind {
+            RollKind::Check => self.exhaustion >= 1,
+            RollKind::Attack | RollKind::Save(_) => self.exhaustion >= 3,
+        };
+        if exhausted {
+            fx.disadvantage.push("exhaustion");
+        }
+        fx
+    }
+
+    /// Takes `amount` damage, spending temporary HP first; returns how much the
+    /// temporary HP absorbed. At 0 HP a `critical` hit counts as two failed death saves.
+    pub fn take_damage(&mut self, amount: i32, critical: bool) -> i32 {
+        let absorbed = amount.clamp(0, self.hp_temp);
+        self.hp_temp -= absorbed;
+        let damage = amount - absorbed;
+        if damage <= 0 {
+            return absorbed;
+        }
+        if damage - self.hp_current >= self.effective_hp_max() {
+            // Massive damage: what's left after dropping to 0 is at least the HP max.
+            self.death_failures = 3;
+        } else if self.hp_current == 0 {
+            self.fail_death_saves(if critical { 2 } else { 1 });
+        }
+        self.hp_current = (self.hp_current - damage).max(0);
+        if self.hp_current == 0 {
+            // Unconscious: concentration ends without a save.
+            self.concentration = None;
+        }
+        absorbed
+    }
+
+    /// DC of the Constitution save to keep concentrating after taking `damage`.
+    pub fn concentration_dc(damage: i32) -> i32 {
+        (damage / 2).max(10)
+    }
+
+    /// Heals up to the (reduced) HP maximum; healing from 0 HP clears death saves.
+    pub fn heal(&mut self, amount: i32) {
+        if amount <= 0 || self.is_dead() {
+            return;
+        }
+        if self.hp_current == 0 {
+            self.reset_death_saves();
+        }
+        self.hp_current = (self.hp_current + amount).min(self.effective_hp_max()).max(self.hp_current);
+    }
+
+    /// Temporary HP don't stack: the higher amount is kept.
+    pub fn gain_temp_hp(&mut self, amount: i32) {
+        self.hp_temp = self.hp_temp.max(amount);
+    }
+
+    /// Sets the HP maximum reduction; a maximum reduced to 0 kills.
+    pub fn set_hp_max_reduction(&mut self, reduction: i32) {
+        self.hp_max_reduction = reduction.clamp(0, self.hp_max);
+        self.hp_current = self.hp_current.min(self.effective_hp_max());
+        if self.effective_hp_max() == 0 {
+            self.death_failures = 3;
+        }
+    }
+
+    pub fn vitality(&self) -> Vitality {
+        if self.is_dead() {
+            Vitality::Dead
+        } else if self.hp_current > 0 {
+            Vitality::Conscious
+        } else if self.stable {
+            Vitality::Stable
+        } else {
+            Vitality::Dying
+        }
+    }
+
+    pub fn is_dead(&self) -> bool {
+        self.death_failures >= 3 || self.exhaustion >= 6
+    }
+
+    pub fn reset_death_saves(&mut self) {
+        self.death_successes = 0;
+        self.death_failures = 0;
+        self.stable = false;
+    }
+
+    /// Adds failed death saves, e.g. 1 for damage at 0 HP or 2 for a critical hit.
+    pub fn fail_death_saves(&mut self, count: i32) {
+        self.stable = false;
+        self.death_failures = (self.death_failures + count).min(3);
+    }
+
+    /// Applies a death saving throw of `total` while dying; `None` if not dying.
+    ///
+    /// 10 or more succeeds, a natural 20 restores 1 HP and a natural 1 counts as
+    /// two failures. Three successes stabilize, three failures kill.
+    pub fn apply_death_save(&mut self, total: i32, natural: Option<Natural>) -> Option<DeathSave> {
+        if self.vitality() != Vitality::Dying {
+            return None;
+        }
+        let outcome = match natural {
+            Some(Natural::Crit) => {
+                self.reset_death_saves();
+                self.hp_current = 1.min(self.effective_hp_max());
+                return Some(DeathSave::Revived);
+            }
+            Some(Natural::Fumble) => {
+                self.fail_death_saves(2);
+                DeathSave::Failure
+            }
+            None if total >= 10 => {
+                self.death_successes += 1;
+                DeathSave::Success
+            }
+            None => {
+                self.fail_death_saves(1);
+                DeathSave::Failure
+            }
+        };
+        if self.is_dead() {
+            Some(DeathSave::Died)
+        } else if self.death_successes >= 3 {
+            self.death_successes = 0;
+            self.death_failures = 0;
+            self.stable = true;
+            Some(DeathSave::Stabilized)
+        } else {
+            Some(outcome)
+        }
+    }
+
+    pub fn set_hp(&mut self, new_hp: i32) {
+        self.hp_current = new_hp.clamp(0, self.effective_hp_max());
+    }
+
+    /// Spends (negative `delta`) or regains slots of a spell level, within 0..=max.
+    /// Level 0 stands for the Pact Magic slots.
     pub fn adjust_spell_slot(&mut self, level: usize, delta: i32) {
+        if level == 0 {
+            self.pact_slots = (self.pact_slots + delta).clamp(0, self.pact_slots_max);
+        } else if (1..=9).contains(&level) {
+            let idx = level - 1;
+            self.spell_slots[idx] = (self.spell_slots[idx] + delta).clamp(0, self.spell_slots_max[idx]);
+        }
+    }
+
+    /// Changes how many slots a spell level has; unspent slots follow along.
+    pub fn adjust_spell_slot_max(&mut self, level: usize, delta: i32) {
         if (1..=9).contains(&level) {
             let idx = level - 1;
-            let new_val = (self.spell_slots[idx] + delta).max(0);
-            self.spell_slots[idx] = new_val;
+            let max = (self.spell_slots_max[idx] + delta).max(0);
+            self.spell_slots[idx] = (self.spell_slots[idx] + delta).clamp(0, max);
+            self.spell_slots_max[idx] = max;
         }
     }
 
     pub fn ability_modifier_by_name(&self, name: &str) -> i32 {
         match name.to_lowercase().as_str() {
-            "str" | "strength" => Self::ability_mod(self.strength),
-            "dex" | "dexterity" => Self::ability_mod(self.dexterity),
-            "con" | "constitution" => Self::ability_mod(self.constitution),
-            "int" | "intelligence" => Self::ability_mod(self.intelligence),
-            "wis" | "wisdom" => Self::ability_mod(self.wisdom),
-            "cha" | "charisma" => Self::ability_mod(self.charisma),
+            "str" | "strength" => self.str_mod(),
+            "dex" | "dexterity" => self.dex_mod(),
+            "con" | "constitution" => self.con_mod(),
+            "int" | "intelligence" => self.int_mod(),
+            "wis" | "wisdom" => self.wis_mod(),
+            "cha" | "charisma" => self.cha_mod(),
             _ => 0,
         }
     }
 
+    /// Value of an `@name` reference in a roll: ability modifiers (`@str`), `@prof`,
+    /// `@level`, and for casters `@spell` (spell attack bonus) and `@dc` (spell save DC).
+    pub fn reference(&self, name: &str) -> Option<i32> {
+        match name {
+            "str" | "strength" | "dex" | "dexterity" | "con" | "constitution" | "int" | "intelligence" | "wis" | "wisdom" | "cha" | "charisma" => {
+                Some(self.ability_modifier_by_name(name))
+            }
+            "prof" => Some(self.proficiency_bonus()),
+            "level" => Some(self.level),
+            "spell" => self.spellcasting_ability().map(|a| self.spell_attack_bonus(a)),
+            "dc" => self.spellcasting_ability().map(|a| self.spell_save_dc(a)),
+            _ => None,
+        }
+    }
+
+    /// Parses a roll expression against this sheet, resolving `@` references.
+    pub fn parse_roll(&self, expr: &str) -> Result<Expr, DiceError> {
+        dice::parse_with(expr, &|name| self.reference(name))
+    }
+
+    pub fn find_macro(&self, name: &str) -> Option<&RollMacro> {
+        self.macros.iter().find(|m| m.name.eq_ignore_ascii_case(name))
+    }
+
+    /// Adds a macro, replacing any macro of the same name.
+    pub fn set_macro(&mut self, name: &str, expression: &str, kind: Option<RollKind>) {
+        let m = RollMacro { name: name.to_lowercase(), expression: expression.trim().to_string(), kind };
+        match self.macros.iter_mut().find(|x| x.name == m.name) {
+            Some(existing) => *existing = m,
+            None => self.macros.push(m),
+        }
+    }
+
+    pub fn remove_macro(&mut self, name: &str) -> bool {
+        let before = self.macros.len();
+        self.macros.retain(|m| !m.name.eq_ignore_ascii_case(name));
+        self.macros.len() != before
+    }
+
+    pub fn find_resource_mut(&mut self, name: &str) -> Option<&mut Resource> {
+        self.resources.iter_mut().find(|r| r.name.eq_ignore_ascii_case(name))
+    }
+
+    /// Adds a resource with all uses available, replacing any of the same name.
+    pub fn set_resource(&mut self, name: &str, max: i32, recharge: Recharge) {
+        let r = Resource { name: name.to_lowercase(), current: max, max, recharge };
+        match self.find_resource_mut(&r.name) {
+            Some(existing) => *existing = r,
+            None => self.resources.push(r),
+        }
+    }
+
+    pub fn remove_resource(&mut self, name: &str) -> bool {
+        let before = self.resources.len();
+        self.resources.retain(|r| !r.name.eq_ignore_ascii_case(name));
+        self.resources.len() != before
+    }
+
+    /// Recharges Pact Magic and short-rest resources. Hit dice are spent separately.
+    pub fn short_rest(&mut self) {
+        self.pact_slots = self.pact_slots_max;
+        for r in self.resources.iter_mut().filter(|r| r.recharge == Recharge::Short) {
+            r.current = r.max;
+        }
+    }
+
+    /// A long rest needs at least 1 HP to do any good.
+    pub fn can_long_rest(&self) -> bool {
+        self.hp_current > 0
+    }
+
+    /// Restores HP, spell slots and every resource, ends HP max reduction and temporary HP,
+    /// lowers exhaustion by one and regains half the total hit dice (at least one).
+    pub fn long_rest(&mut self) {
+        if !self.can_long_rest() {
+            return;
+        }
+        for r in self.resources.iter_mut() {
+            r.current = r.max;
+        }
+        self.spell_slots = self.spell_slots_max.clone();
+        self.pact_slots = self.pact_slots_max;
+        self.hp_max_reduction = 0;
+        self.hp_temp = 0;
+        self.exhaustion = (self.exhaustion - 1).max(0);
+        self.hp_current = self.effective_hp_max();
+        self.reset_death_saves();
+        self.regain_hit_dice((self.level / 2).max(1));
+    }
+
+    /// Spellcasting ability of the first spellcasting class.
+    pub fn spellcasting_ability(&self) -> Option<&'static str> {
+        self.spellcasting_abilities().first().copied()
+    }
+
+    /// Distinct spellcasting abilities over all classes, in class order.
+    pub fn spellcasting_abilities(&self) -> Vec<&'static str> {
+        let mut abilities = Vec::new();
+        for ability in self.class_levels().iter().filter_map(|(class, _)| spellcasting_ability(class)) {
+            if !abilities.contains(&ability) {
+                abilities.push(ability);
+            }
+        }
+        abilities
+    }
+
+    /// `8 + proficiency bonus + ability modifier`.
+    pub fn spell_save_dc(&self, ability: &str) -> i32 {
+        8 + self.spell_attack_bonus(ability)
+    }
+
+    pub fn spell_attack_bonus(&self, ability: &str) -> i32 {
+        self.proficiency_bonus() + self.ability_modifier_by_name(ability)
+    }
+
+    /// How many leveled spells can be prepared, adding up every class that
+    /// prepares spells; `None` when no class does (they know their spells instead).
+    pub fn prepared_limit(&self) -> Option<i32> {
+        let limits: Vec<i32> = self
+            .class_levels()
+            .iter()
+            .filter_map(|(class, level)| {
+                let prepared_level = match first_word(class).as_str() {
+                    _ if !prepares_spells(class) => return None,
+                    "paladin" => level / 2,
+                    _ => *level,
+                };
+                let ability = spellcasting_ability(class)?;
+                Some((prepared_level + self.ability_modifier_by_name(ability)).max(1))
+            })
+            .collect();
+        if limits.is_empty() { None } else { Some(limits.iter().sum()) }
+    }
+
+    /// Whether `spell` has to be prepared to be cast from a slot. Cantrips never do,
+    /// nor do spells of a class that knows its spells; a spell with no class only
+    /// does when every spellcasting class of the character prepares spells.
+    pub fn must_prepare(&self, spell: &Spell) -> bool {
+        if spell.is_cantrip() {
+            return false;
+        }
+        if !spell.class.is_empty() {
+            return prepares_spells(&spell.class);
+        }
+        let classes = self.class_levels();
+        let mut casters = classes.iter().map(|(class, _)| class).filter(|class| spellcasting_ability(class).is_some()).peekable();
+        casters.peek().is_some() && casters.all(|class| prepares_spells(class))
+    }
+
+    /// Saving throw modifier for an ability, with proficiency bonus if proficient.
+    pub fn save_modifier(&self, ability: &str) -> i32 {
+        let proficient = ability_index(ability).is_some_and(|i| self.save_proficiencies[i]);
+        self.ability_modifier_by_name(ability) + if proficient { self.proficiency_bonus() } else { 0 }
+    }
+
+    /// Applies immunity, resistance and vulnerability to `amount` damage of `kind`.
+    pub fn resolve_damage(&self, amount: i32, kind: Option<DamageType>) -> ResolvedDamage {
+        let has = |list: &[DamageType]| kind.is_some_and(|k| list.contains(&k));
+        let (immune, resisted, vulnerable) = (has(&self.immunities), has(&self.resistances), has(&self.vulnerabilities));
+        let total = if immune {
+            0
+        } else {
+            // Resistance is applied before vulnerability, so having both still rounds down.
+            let halved = if resisted { amount / 2 } else { amount };
+            if vulnerable { halved * 2 } else { halved }
+        };
+        ResolvedDamage { amount, kind, immune, resisted, vulnerable, total }
+    }
+
     pub fn skill_modifier(&self, skill: &str) -> i32 {
         let (ability, key) = skill_to_ability(skill);
         let base = self.ability_modifier_by_name(ability);
@@ -124,6 +738,191 @@ impl Character {
     }
 }
 
+/// Ability abbreviations in sheet order.
+pub const ABILITIES: [&str; 6] = ["str", "dex", "con", "int", "wis", "cha"];
+
+/// Index into [`ABILITIES`] of a short or long ability name.
+pub fn ability_index(name: &str) -> Option<usize> {
+    match name.to_lowercase().as_str() {
+        "str" | "strength" => Some(0),
+        "dex" | "dexterity" => Some(1),
+        "con" | "constitution" => Some(2),
+        "int" | "intelligence" => Some(3),
+        "wis" | "wisdom" => Some(4),
+        "cha" | "charisma" => Some(5),
+        _ => None,
+    }
+}
+
+/// Standard saving throw proficiencies of the SRD classes; none for unknown classes.
+/// A multiclass character only gets those of the first class.
+pub fn class_save_proficiencies(class_name: &str) -> [bool; 6] {
+    let first = parse_class_levels(class_name).map_or_else(|| class_name.to_string(), |c| c[0].0.clone());
+    let saves: &[usize] = match first_word(&first).as_str() {
+        "barbarian" | "fighter" => &[0, 2],
+        "bard" => &[1, 5],
+        "cleric" | "paladin" | "warlock" => &[4, 5],
+        "druid" | "wizard" => &[3, 4],
+        "monk" | "ranger" => &[0, 1],
+        "rogue" => &[1, 3],
+        "sorcerer" => &[2, 5],
+        _ => &[],
+    };
+    let mut flags = [false; 6];
+    for &i in saves {
+        flags[i] = true;
+    }
+    flags
+}
+
+/// Parses multiclass notation like `Paladin 2 / Sorcerer 3`; `None` for a plain class name.
+pub fn parse_class_levels(class_name: &str) -> Option<Vec<(String, i32)>> {
+    class_name
+        .split('/')
+        .map(|part| {
+            let (name, level) = part.trim().rsplit_once(char::is_whitespace)?;
+            let level = level.parse::<i32>().ok().filter(|l| *l > 0)?;
+            Some((name.trim().to_string(), level))
+        })
+        .collect()
+}
+
+/// `fighter` for `Fighter (Eldritch Knight)`.
+fn first_word(class_name: &str) -> String {
+    class_name.split_whitespace().next().unwrap_or_default().to_lowercase()
+}
+
+fn format_class_levels(classes: &[(String, i32)]) -> String {
+    classes.iter().map(|(name, level)| format!("{} {}", name, level)).collect::<Vec<_>>().join(" / ")
+}
+
+/// How a class progresses through the spell slot table.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Caster {
+    Full,
+    Half,
+    Third,
+}
+
+/// Slot progression of the SRD classes; Eldritch Knights and Arcane Tricksters
+/// count when the subclass is part of the class name. Warlocks use Pact Magic.
+pub fn caster_kind(class_name: &str) -> Option<Caster> {
+    let class = class_name.to_lowercase();
+    match first_word(class_name).as_str() {
+        "bard" | "cleric" | "druid" | "sorcerer" | "wizard" => Some(Caster::Full),
+        "paladin" | "ranger" => Some(Caster::Half),
+        _ if class.contains("eldritch knight") || class.contains("arcane trickster") => Some(Caster::Third),
+        _ => None,
+    }
+}
+
+/// Level to look up in the spell slot table. A single spellcasting class uses
+/// its own table; multiclass casters add full levels, half of half-caster levels
+/// and a third of third-caster levels, rounded down.
+pub fn spellcaster_level(classes: &[(String, i32)]) -> i32 {
+    let casters: Vec<(Caster, i32)> = classes.iter().filter_map(|(c, l)| caster_kind(c).map(|k| (k, *l))).collect();
+    if let [(kind, level)] = casters[..] {
+        return match kind {
+            Caster::Full => level,
+            Caster::Half if level >= 2 => (level + 1) / 2,
+            Caster::Third if level >= 3 => (level + 2) / 3,
+            _ => 0,
+        };
+    }
+    casters
+        .iter()
+        .map(|(kind, level)| match kind {
+            Caster::Full => *level,
+            Caster::Half => level / 2,
+            Caster::Third => level / 3,
+        })
+        .sum()
+}
+
+/// The SRD spell slot table, by spellcaster level.
+const SPELL_SLOTS: [[i32; 9]; 20] = [
+    [2, 0, 0, 0, 0, 0, 0, 0, 0],
+    [3, 0, 0, 0, 0, 0, 0, 0, 0],
+    [4, 2, 0, 0, 0, 0, 0, 0, 0],
+    [4, 3, 0, 0, 0, 0, 0, 0, 0],
+    [4, 3, 2, 0, 0, 0, 0, 0, 0],
+    [4, 3, 3, 0, 0, 0, 0, 0, 0],
+    [4, 3, 3, 1, 0, 0, 0, 0, 0],
+    [4, 3, 3, 2, 0, 0, 0, 0, 0],
+    [4, 3, 3, 3, 1, 0, 0, 0, 0],
+    [4, 3, 3, 3, 2, 0, 0, 0, 0],
+    [4, 3, 3, 3, 2, 1, 0, 0, 0],
+    [4, 3, 3, 3, 2, 1, 0, 0, 0],
+    [4, 3, 3, 3, 2, 1, 1, 0, 0],
+    [4, 3, 3, 3, 2, 1, 1, 0, 0],
+    [4, 3, 3, 3, 2, 1, 1, 1, 0],
+    [4, 3, 3, 3, 2, 1, 1, 1, 0],
+    [4, 3, 3, 3, 2, 1, 1, 1, 1],
+    [4, 3, 3, 3, 3, 1, 1, 1, 1],
+    [4, 3, 3, 3, 3, 2, 1, 1, 1],
+    [4, 3, 3, 3, 3, 2, 2, 1, 1],
+];
+
+pub fn spell_slots_for(caster_level: i32) -> [i32; 9] {
+    match caster_level {
+        l if l < 1 => [0; 9],
+        l => SPELL_SLOTS[l.min(20) as usize - 1],
+    }
+}
+
+/// Ability the SRD spellcasting classes cast with.
+pub fn spellcasting_ability(class_name: &str) -> Option<&'static str> {
+    match first_word(class_name).as_str() {
+        "bard" | "paladin" | "sorcerer" | "warlock" => Some("cha"),
+        "cleric" | "druid" | "ranger" => Some("wis"),
+        "wizard" => Some("int"),
+        _ if caster_kind(class_name) == Some(Caster::Third) => Some("int"),
+        _ => None,
+    }
+}
+
+/// Whether a class prepares its spells each day rather than knowing a fixed list.
+pub fn prepares_spells(class_name: &str) -> bool {
+    matches!(first_word(class_name).as_str(), "cleric" | "druid" | "paladin" | "wizard")
+}
+
+/// Pact Magic `(slots, slot level)` for a warlock level; warlock levels don't
+/// count towards the multiclass spellcaster level.
+pub fn pact_slots_for(warlock_level: i32) -> (i32, i32) {
+    match warlock_level {
+        l if l < 1 => (0, 0),
+        1 => (1, 1),
+        l => {
+            let count = match l { 2..=10 => 2, 11..=16 => 3, _ => 4 };
+            (count, ((l + 1) / 2).min(5))
+        }
+    }
+}
+
+/// Parses the hit dice to spend on a short rest: counts like `2` (largest dice
+/// first) or per die size like `1d10 2d6`, as `(count, size)` pairs.
+pub fn parse_hit_dice(input: &str) -> Option<Vec<(i32, Option<i32>)>> {
+    let count = |n: &str| if n.is_empty() { Some(1) } else { n.parse::<i32>().ok().filter(|n| *n >= 0) };
+    input
+        .split([',', ' '])
+        .filter(|part| !part.is_empty())
+        .map(|part| match part.split_once('d') {
+            Some((n, size)) => Some((count(n)?, Some(size.parse().ok()?))),
+            None => Some((part.parse().ok().filter(|n| *n >= 0)?, None)),
+        })
+        .collect()
+}
+
+/// Hit die of the SRD classes; d8 for unknown classes.
+pub fn hit_die_size(class_name: &str) -> i32 {
+    match first_word(class_name).as_str() {
+        "barbarian" => 12,
+        "fighter" | "paladin" | "ranger" => 10,
+        "sorcerer" | "wizard" => 6,
+        _ => 8,
+    }
+}
+
 pub fn all_skills() -> Vec<(&'static str, &'static str)> {
     vec![
         ("acrobatics", "dex"),
@@ -156,3 +955,1064 @@ pub fn skill_to_ability(skill: &str) -> (&'static str, String) {
     }
     ("str", "".to_string())
 }
+
+/// The thirteen damage types of the SRD.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum DamageType {
+    Acid,
+    Bludgeoning,
+    Cold,
+    Fire,
+    Force,
+    Lightning,
+    Necrotic,
+    Piercing,
+    Poison,
+    Psychic,
+    Radiant,
+    Slashing,
+    Thunder,
+}
+
+impl DamageType {
+    pub const ALL: [DamageType; 13] = [
+        Self::Acid,
+        Self::Bludgeoning,
+        Self::Cold,
+        Self::Fire,
+        Self::Force,
+        Self::Lightning,
+        Self::Necrotic,
+        Self::Piercing,
+        Self::Poison,
+        Self::Psychic,
+        Self::Radiant,
+        Self::Slashing,
+        Self::Thunder,
+    ];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Self::Acid => "acid",
+            Self::Bludgeoning => "bludgeoning",
+            Self::Cold => "cold",
+            Self::Fire => "fire",
+            Self::Force => "force",
+            Self::Lightning => "lightning",
+            Self::Necrotic => "necrotic",
+            Self::Piercing => "piercing",
+            Self::Poison => "poison",
+            Self::Psychic => "psychic",
+            Self::Radiant => "radiant",
+            Self::Slashing => "slashing",
+            Self::Thunder => "thunder",
+        }
+    }
+
+    pub fn from_name(name: &str) -> Option<Self> {
+        let name = name.trim().to_lowercase();
+        Self::ALL.into_iter().find(|t| t.name() == name)
+    }
+}
+
+impl fmt::Display for DamageType {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.name())
+    }
+}
+
+/// The conditions of the SRD; exhaustion is tracked separately as a level.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Condition {
+    Blinded,
+    Charmed,
+    Deafened,
+    Frightened,
+    Grappled,
+    Incapacitated,
+    Invisible,
+    Paralyzed,
+    Petrified,
+    Poisoned,
+    Prone,
+    Restrained,
+    Stunned,
+    Unconscious,
+}
+
+impl Condition {
+    pub const ALL: [Condition; 14] = [
+        Self::Blinded,
+        Self::Charmed,
+        Self::Deafened,
+        Self::Frightened,
+        Self::Grappled,
+        Self::Incapacitated,
+        Self::Invisible,
+        Self::Paralyzed,
+        Self::Petrified,
+        Self::Poisoned,
+        Self::Prone,
+        Self::Restrained,
+        Self::Stunned,
+        Self::Unconscious,
+    ];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Self::Blinded => "blinded",
+            Self::Charmed => "charmed",
+            Self::Deafened => "deafened",
+            Self::Frightened => "frightened",
+            Self::Grappled => "grappled",
+            Self::Incapacitated => "incapacitated",
+            Self::Invisible => "invisible",
+            Self::Paralyzed => "paralyzed",
+            Self::Petrified => "petrified",
+            Self::Poisoned => "poisoned",
+            Self::Prone => "prone",
+            Self::Restrained => "restrained",
+            Self::Stunned => "stunned",
+            Self::Unconscious => "unconscious",
+        }
+    }
+
+    pub fn from_name(name: &str) -> Option<Self> {
+        let name = name.trim().to_lowercase();
+        Self::ALL.into_iter().find(|c| c.name() == name)
+    }
+
+    /// Conditions that drop speed to 0.
+    pub fn stops_movement(self) -> bool {
+        matches!(
+            self,
+            Self::Grappled | Self::Paralyzed | Self::Petrified | Self::Restrained | Self::Stunned | Self::Unconscious
+        )
+    }
+}
+
+impl fmt::Display for Condition {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.name())
+    }
+}
+
+/// Effects of each exhaustion level; every level includes those below it.
+pub const EXHAUSTION_EFFECTS: [&str; 6] = [
+    "disadvantage on ability checks",
+    "speed halved",
+    "disadvantage on attacks and saves",
+    "HP max halved",
+    "speed 0",
+    "death",
+];
+
+/// What a d20 roll is for, which decides the conditions that affect it.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum RollKind {
+    /// Skill and ability checks
+    Check,
+    Attack,
+    /// Saving throw by index into [`ABILITIES`]; `None` for death saves
+    Save(Option<usize>),
+}
+
+impl RollKind {
+    /// Splits a trailing `attack` or `check` tag off a roll, e.g. `1d20+7 attack`.
+    pub fn split_tag(input: &str) -> (&str, Option<Self>) {
+        let input = input.trim();
+        for kind in [Self::Attack, Self::Check] {
+            if let Some(roll) = input.strip_suffix(kind.tag()) {
+                return (roll.trim_end(), Some(kind));
+            }
+        }
+        (input, None)
+    }
+
+    /// The tag [`split_tag`](Self::split_tag) reads, with its leading space.
+    pub fn tag(self) -> &'static str {
+        match self {
+            Self::Check => " check",
+            Self::Attack => " attack",
+            Self::Save(_) => "",
+        }
+    }
+}
+
+/// Sources of advantage, disadvantage and automatic failure on a roll.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct RollEffects {
+    pub advantage: Vec<&'static str>,
+    pub disadvantage: Vec<&'static str>,
+    pub auto_fail: Vec<&'static str>,
+}
+
+impl RollEffects {
+    /// Combines the effects with the mode asked for; advantage and disadvantage
+    /// from any number of sources cancel out.
+    pub fn mode(&self, asked: RollMode) -> RollMode {
+        let adv = asked == RollMode::Advantage || !self.advantage.is_empty();
+        let dis = asked == RollMode::Disadvantage || !self.disadvantage.is_empty();
+        match (adv, dis) {
+            (true, false) => RollMode::Advantage,
+            (false, true) => RollMode::Disadvantage,
+            _ => RollMode::Normal,
+        }
+    }
+
+    /// E.g. ` [dis: poisoned, exhaustion]`, empty without effects.
+    pub fn note(&self) -> String {
+        let parts: Vec<String> = [("adv", &self.advantage), ("dis", &self.disadvantage), ("fail", &self.auto_fail)]
+            .iter()
+            .filter(|(_, sources)| !sources.is_empty())
+            .map(|(word, sources)| format!("{}: {}", word, sources.join(", ")))
+            .collect();
+        if parts.is_empty() { String::new() } else { format!(" [{}]", parts.join("; ")) }
+    }
+}
+
+/// Damage after the target's immunities, resistances and vulnerabilities.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ResolvedDamage {
+    pub amount: i32,
+    pub kind: Option<DamageType>,
+    pub immune: bool,
+    pub resisted: bool,
+    pub vulnerable: bool,
+    pub total: i32,
+}
+
+impl fmt::Display for ResolvedDamage {
+    /// E.g. `12 fire ÷ 2 (resistant) = 6`.
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.amount)?;
+        if let Some(kind) = self.kind {
+            write!(f, " {}", kind)?;
+        }
+        if self.immune {
+            f.write_str(" (immune)")?;
+        } else {
+            if self.resisted {
+                f.write_str(" ÷ 2 (resistant)")?;
+            }
+            if self.vulnerable {
+                f.write_str(" × 2 (vulnerable)")?;
+            }
+        }
+        write!(f, " = {}", self.total)
+    }
+}
+
+/// A spell a character knows, kept in the `spells` table.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Spell {
+    pub id: Option<i64>,
+    pub character_id: Option<i64>,
+    pub name: String,
+    /// 0 for cantrips
+    pub level: i32,
+    /// The class it's cast as, which decides whether it must be prepared; empty if unset
+    pub class: String,
+    pub school: String,
+    pub casting_time: String,
+    pub range: String,
+    pub components: String,
+    pub duration: String,
+    pub concentration: bool,
+    pub ritual: bool,
+    pub description: String,
+    pub prepared: bool,
+}
+
+impl Spell {
+    /// Parses `name | level | class | school | casting time | range | components | duration | flags | description`,
+    /// where only name and level are required and flags may contain `c`
+    /// (concentration) and `r` (ritual).
+    pub fn parse(input: &str) -> Result<Self, String> {
+        let fields: Vec<&str> = input.splitn(10, '|').map(str::trim).collect();
+        let field = |i: usize| fields.get(i).copied().unwrap_or_default().to_string();
+        let name = field(0);
+        if name.is_empty() {
+            return Err(String::from("A spell needs a name"));
+        }
+        let level = match fields.get(1) {
+            Some(l) => l.parse::<i32>().ok().filter(|l| (0..=9).contains(l)).ok_or_else(|| format!("'{}' isn't a spell level from 0 to 9", l))?,
+            None => return Err(String::from("Use name | level | class | school | casting time | range | components | duration | flags | description")),
+        };
+        let flags = field(8).to_lowercase();
+        Ok(Self {
+            id: None,
+            character_id: None,
+            name,
+            level,
+            class: field(2),
+            school: field(3),
+            casting_time: field(4),
+            range: field(5),
+            components: field(6),
+            duration: field(7),
+            concentration: flags.contains('c'),
+            ritual: flags.contains('r'),
+            description: field(9),
+            prepared: false,
+        })
+    }
+
+    pub fn is_cantrip(&self) -> bool {
+        self.level == 0
+    }
+}
+
+/// How weighed down a character is; see [`Character::encumbrance`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Encumbrance {
+    #[default]
+    Unencumbered,
+    /// Variant rule: speed -10 ft
+    Encumbered,
+    /// Variant rule: speed -20 ft
+    HeavilyEncumbered,
+    /// Past the carrying capacity: can only push or drag, at 5 ft
+    OverCapacity,
+}
+
+impl Encumbrance {
+    pub fn label(self) -> &'static str {
+        match self {
+            Self::Unencumbered => "unencumbered",
+            Self::Encumbered => "encumbered",
+            Self::HeavilyEncumbered => "heavily encumbered",
+            Self::OverCapacity => "over capacity",
+        }
+    }
+
+    pub fn speed_penalty(self) -> i32 {
+        match self {
+            Self::Encumbered => 10,
+            Self::HeavilyEncumbered => 20,
+            _ => 0,
+        }
+    }
+}
+
+/// A coin denomination of the SRD.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Coin {
+    Cp,
+    Sp,
+    Ep,
+    Gp,
+    Pp,
+}
+
+impl Coin {
+    /// Lowest to highest.
+    pub const ALL: [Coin; 5] = [Self::Cp, Self::Sp, Self::Ep, Self::Gp, Self::Pp];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Self::Cp => "cp",
+            Self::Sp => "sp",
+            Self::Ep => "ep",
+            Self::Gp => "gp",
+            Self::Pp => "pp",
+        }
+    }
+
+    pub fn from_name(name: &str) -> Option<Self> {
+        let name = name.trim().to_lowercase();
+        Self::ALL.into_iter().find(|c| c.name() == name)
+    }
+
+    /// Worth in copper pieces.
+    pub fn value(self) -> i64 {
+        match self {
+            Self::Cp => 1,
+            Self::Sp => 10,
+            Self::Ep => 50,
+            Self::Gp => 100,
+            Self::Pp => 1000,
+        }
+    }
+}
+
+/// Parses amounts like `3 gp 5 sp` or `25gp`.
+pub fn parse_coins(input: &str) -> Result<Vec<(i64, Coin)>, String> {
+    let mut coins = Vec::new();
+    let mut words = input.split_whitespace().peekable();
+    while let Some(word) = words.next() {
+        let digits = word.find(|ch: char| !ch.is_ascii_digit()).unwrap_or(word.len());
+        let (amount, unit) = word.split_at(digits);
+        let amount: i64 = amount.parse().map_err(|_| format!("'{}' isn't an amount of coins", word))?;
+        let unit = if unit.is_empty() { words.next().unwrap_or_default() } else { unit };
+        let coin = Coin::from_name(unit).ok_or_else(|| format!("'{}' isn't a coin (cp, sp, ep, gp, pp)", unit))?;
+        coins.push((amount, coin));
+    }
+    if coins.is_empty() {
+        return Err(String::from("Give an amount like 3 gp 5 sp"));
+    }
+    Ok(coins)
+}
+
+/// Coins a character carries.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Purse {
+    pub cp: i64,
+    pub sp: i64,
+    pub ep: i64,
+    pub gp: i64,
+    pub pp: i64,
+}
+
+impl Purse {
+    pub fn count(&self, coin: Coin) -> i64 {
+        match coin {
+            Coin::Cp => self.cp,
+            Coin::Sp => self.sp,
+            Coin::Ep => self.ep,
+            Coin::Gp => self.gp,
+            Coin::Pp => self.pp,
+        }
+    }
+
+    fn count_mut(&mut self, coin: Coin) -> &mut i64 {
+        match coin {
+            Coin::Cp => &mut self.cp,
+            Coin::Sp => &mut self.sp,
+            Coin::Ep => &mut self.ep,
+            Coin::Gp => &mut self.gp,
+            Coin::Pp => &mut self.pp,
+        }
+    }
+
+    pub fn total_cp(&self) -> i64 {
+        Coin::ALL.iter().fold(0i64, |sum, c| sum.saturating_add(self.count(*c).saturating_mul(c.value())))
+    }
+
+    pub fn total_gp(&self) -> f64 {
+        self.total_cp() as f64 / 100.0
+    }
+
+    /// Coins weigh 1 lb per 50.
+    pub fn weight(&self) -> f64 {
+        Coin::ALL.iter().map(|c| self.count(*c)).sum::<i64>() as f64 / 50.0
+    }
+
+    /// Adds coins, as long as the purse stays worth no more than `i64::MAX` cp.
+    pub fn add(&mut self, amount: i64, coin: Coin) -> Result<(), String> {
+        cp_worth(amount, coin)?.checked_add(self.total_cp()).ok_or_else(|| String::from("Amount too large"))?;
+        *self.count_mut(coin) += amount;
+        Ok(())
+    }
+
+    /// Pays `amount` of `coin`: with those coins first, then by breaking the
+    /// smallest higher coins, then with lower coins. Change comes back in gp, sp and cp.
+    pub fn spend(&mut self, amount: i64, coin: Coin) -> Result<(), String> {
+        let mut owed = cp_worth(amount, coin)?;
+        if owed > self.total_cp() {
+            return Err(format!("Not enough coins: {} {} costs more than the {}", amount, coin.name(), self));
+        }
+        let higher = Coin::ALL.into_iter().filter(|c| c.value() > coin.value());
+        let lower = Coin::ALL.into_iter().rev().filter(|c| c.value() < coin.value());
+        for pay in std::iter::once(coin).chain(higher).chain(lower) {
+            if owed == 0 {
+                break;
+            }
+            let used = self.count(pay).min(owed / pay.value() + i64::from(owed % pay.value() != 0));
+            *self.count_mut(pay) -= used;
+            let paid = used * pay.value();
+            if paid > owed {
+                self.give_change(paid - owed, pay);
+            }
+            owed -= paid.min(owed);
+        }
+        Ok(())
+    }
+
+    /// Adds `cp` worth of gp, sp and cp, each worth less than `broken`.
+    fn give_change(&mut self, mut cp: i64, broken: Coin) {
+        for coin in [Coin::Gp, Coin::Sp, Coin::Cp].into_iter().filter(|c| c.value() < broken.value()) {
+            *self.count_mut(coin) += cp / coin.value();
+            cp %= coin.value();
+        }
+    }
+
+    /// Exchanges `amount` coins of `from` for `to`; going up, coins that don't
+    /// make a whole coin stay behind. Returns how many `to` coins came out.
+    pub fn convert(&mut self, amount: i64, from: Coin, to: Coin) -> Result<i64, String> {
+        if amount > self.count(from) {
+            return Err(format!("Only {} {} in the purse", self.count(from), from.name()));
+        }
+        let got = cp_worth(amount, from)? / to.value();
+        if got == 0 {
+            return Err(format!("{} {} doesn't make a whole {}", amount, from.name(), to.name()));
+        }
+        *self.count_mut(from) -= got * to.value() / from.value();
+        *self.count_mut(to) += got;
+        Ok(got)
+    }
+}
+
+/// Worth of `amount` coins in cp, unless that overflows.
+fn cp_worth(amount: i64, coin: Coin) -> Result<i64, String> {
+    amount.checked_mul(coin.value()).ok_or_else(|| String::from("Amount too large"))
+}
+
+impl fmt::Display for Purse {
+    /// E.g. `3 pp, 25 gp, 4 sp`, highest first, or `no coins`.
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let coins: Vec<String> = Coin::ALL.iter().rev().filter(|c| self.count(**c) != 0).map(|c| format!("{} {}", self.count(*c), c.name())).collect();
+        if coins.is_empty() { f.write_str("no coins") } else { f.write_str(&coins.join(", ")) }
+    }
+}
+
+/// An inventory entry, kept in the `items` table.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Item {
+    pub id: Option<i64>,
+    pub character_id: Option<i64>,
+    pub name: String,
+    pub quantity: i32,
+    /// Weight of one, in pounds
+    pub weight: f64,
+    /// Value of one, in gold pieces
+    pub value: f64,
+    pub description: String,
+    pub equipped: bool,
+    pub attuned: bool,
+    pub tags: Vec<String>,
+}
+
+impl Item {
+    /// A single item with nothing but a name, as old string inventories were.
+    pub fn named(name: &str) -> Self {
+        Self {
+            id: None,
+            character_id: None,
+            name: name.trim().to_string(),
+            quantity: 1,
+            weight: 0.0,
+            value: 0.0,
+            description: String::new(),
+            equipped: false,
+            attuned: false,
+            tags: vec![],
+        }
+    }
+
+    /// Parses `name | quantity | weight | value | flags | tags | description`, where only
+    /// the name is required, weight may end in `lb`, value may end in `cp`, `sp`, `ep`,
+    /// `gp` or `pp` (gp otherwise), flags may contain `e` (equipped) and `a` (attuned)
+    /// and tags are comma-separated.
+    pub fn parse(input: &str) -> Result<Self, String> {
+        let fields: Vec<&str> = input.splitn(7, '|').map(str::trim).collect();
+        let field = |i: usize| fields.get(i).copied().unwrap_or_default();
+        let mut item = Self::named(field(0));
+        if item.name.is_empty() {
+            return Err(String::from("An item needs a name"));
+        }
+        if !field(1).is_empty() {
+            item.quantity = field(1).parse::<i32>().ok().filter(|q| *q > 0).ok_or_else(|| format!("'{}' isn't a quantity", field(1)))?;
+        }
+        if !field(2).is_empty() {
+            let weight = field(2).trim_end_matches("lbs").trim_end_matches("lb").trim();
+            item.weight = weight.parse::<f64>().ok().filter(|w| *w >= 0.0).ok_or_else(|| format!("'{}' isn't a weight in lb", field(2)))?;
+        }
+        if !field(3).is_empty() {
+            item.value = parse_gp(field(3)).ok_or_else(|| format!("'{}' isn't a value like 50 gp or 5 sp", field(3)))?;
+        }
+        let flags = field(4).to_lowercase();
+        item.equipped = flags.contains('e');
+        item.attuned = flags.contains('a');
+        item.tags = field(5).split(',').map(str::trim).filter(|t| !t.is_empty()).map(String::from).collect();
+        item.description = field(6).to_string();
+        Ok(item)
+    }
+
+    pub fn total_weight(&self) -> f64 {
+        self.weight * self.quantity as f64
+    }
+}
+
+/// Value in gp of `50`, `50 gp`, `5 sp` and so on.
+fn parse_gp(input: &str) -> Option<f64> {
+    let input = input.trim().to_lowercase();
+    let (amount, per_gp) = [("cp", 100.0), ("sp", 10.0), ("ep", 2.0), ("gp", 1.0), ("pp", 0.1)]
+        .into_iter()
+        .find_map(|(suffix, per_gp)| Some((input.strip_suffix(suffix)?, per_gp)))
+        .unwrap_or((input.as_str(), 1.0));
+    amount.trim().parse::<f64>().ok().filter(|v| *v >= 0.0).map(|v| v / per_gp)
+}
+
+/// What a history entry records: a dice roll, or an event logged between rolls
+/// that has no dice of its own.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum RecordKind {
+    Roll,
+    Cast,
+    Damage,
+}
+
+impl RecordKind {
+    /// Key stored in the `kind` column of the `rolls` table.
+    pub fn key(self) -> &'static str {
+        match self {
+            RecordKind::Roll => "roll",
+            RecordKind::Cast => "cast",
+            RecordKind::Damage => "damage",
+        }
+    }
+
+    pub fn from_key(key: &str) -> Self {
+        match key {
+            "cast" => RecordKind::Cast,
+            "damage" => RecordKind::Damage,
+            _ => RecordKind::Roll,
+        }
+    }
+}
+
+/// A logged roll, kept in the `rolls` table so past results can be looked up.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RollRecord {
+    pub id: Option<i64>,
+    pub character_id: Option<i64>,
+    /// Local time as `YYYY-MM-DD HH:MM:SS`, filled in by the database.
+    pub timestamp: String,
+    pub kind: RecordKind,
+    /// What was rolled, e.g. "Stealth check (adv)".
+    pub label: String,
+    pub expression: String,
+    pub dice: Vec<DiceGroup>,
+    pub modifier: i32,
+    pub total: i32,
+    /// Full breakdown as shown in the status line.
+    pub breakdown: String,
+}
+
+impl RollRecord {
+    pub fn new(character_id: Option<i64>, label: &str, result: &RollResult) -> Self {
+        Self {
+            id: None,
+            character_id,
+            timestamp: String::new(),
+            kind: RecordKind::Roll,
+            label: label.to_string(),
+            expression: result.expr.to_string(),
+            dice: result.groups.clone(),
+            modifier: result.modifier(),
+            total: result.total,
+            breakdown: result.to_string(),
+        }
+    }
+
+    /// Logs casting a spell; the slot it used is only part of the breakdown.
+    pub fn cast(character_id: Option<i64>, spell: &Spell, breakdown: &str) -> Self {
+        Self {
+            id: None,
+            character_id,
+            timestamp: String::new(),
+            kind: RecordKind::Cast,
+            label: format!("Cast {}", spell.name),
+            expression: String::new(),
+            dice: vec![],
+            modifier: 0,
+            total: 0,
+            breakdown: breakdown.to_string(),
+        }
+    }
+
+    /// Logs damage taken, which involves no dice of its own.
+    pub fn damage(character_id: Option<i64>, damage: &ResolvedDamage) -> Self {
+        let expression = match damage.kind {
+            Some(kind) => format!("{} {}", damage.amount, kind),
+            None => damage.amount.to_string(),
+        };
+        Self {
+            id: None,
+            character_id,
+            timestamp: String::new(),
+            kind: RecordKind::Damage,
+            label: String::from("Damage taken"),
+            expression,
+            dice: vec![],
+            modifier: 0,
+            total: damage.total,
+            breakdown: damage.to_string(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn death_saves_stabilize_revive_or_kill() {
+        let mut c = Character { hp_current: 0, ..Character::default() };
+        assert_eq!(c.vitality(), Vitality::Dying);
+        assert_eq!(c.apply_death_save(12, None), Some(DeathSave::Success));
+        assert_eq!(c.apply_death_save(4, None), Some(DeathSave::Failure));
+        assert_eq!(c.apply_death_save(10, None), Some(DeathSave::Success));
+        assert_eq!(c.apply_death_save(15, None), Some(DeathSave::Stabilized));
+        assert_eq!((c.vitality(), c.death_successes, c.death_failures), (Vitality::Stable, 0, 0));
+        assert_eq!(c.apply_death_save(15, None), None);
+
+        let mut c = Character { hp_current: 0, ..Character::default() };
+        assert_eq!(c.apply_death_save(1, Some(Natural::Fumble)), Some(DeathSave::Failure));
+        assert_eq!(c.death_failures, 2);
+        assert_eq!(c.apply_death_save(20, Some(Natural::Crit)), Some(DeathSave::Revived));
+        assert_eq!((c.vitality(), c.hp_current, c.death_failures), (Vitality::Conscious, 1, 0));
+
+        let mut c = Character { hp_current: 0, death_failures: 2, ..Character::default() };
+        assert_eq!(c.apply_death_save(3, None), Some(DeathSave::Died));
+        assert_eq!(c.vitality(), Vitality::Dead);
+        c.heal(5);
+        assert_eq!(c.hp_current, 0);
+    }
+
+    #[test]
+    fn damage_at_zero_hp_fails_death_saves() {
+        let mut c = Character { hp_current: 0, stable: true, ..Character::default() };
+        c.take_damage(3, false);
+        assert_eq!((c.vitality(), c.death_failures), (Vitality::Dying, 1));
+        c.heal(2);
+        assert_eq!((c.vitality(), c.hp_current, c.death_failures), (Vitality::Conscious, 2, 0));
+    }
+
+    #[test]
+    fn critical_hits_at_zero_hp_fail_two_death_saves() {
+        let mut c = Character { hp_current: 0, ..Character::default() };
+        c.take_damage(3, true);
+        assert_eq!((c.vitality(), c.death_failures), (Vitality::Dying, 2));
+        c.take_damage(3, true);
+        assert_eq!((c.vitality(), c.death_failures), (Vitality::Dead, 3));
+        let mut c = Character::default();
+        c.take_damage(3, true);
+        assert_eq!((c.hp_current, c.death_failures), (7, 0));
+    }
+
+    #[test]
+    fn massive_damage_kills_outright() {
+        let mut c = Character::default();
+        c.take_damage(19, false);
+        assert_eq!((c.vitality(), c.hp_current), (Vitality::Dying, 0));
+        let mut c = Character::default();
+        c.take_damage(20, false);
+        assert_eq!(c.vitality(), Vitality::Dead);
+        let mut c = Character { hp_current: 0, ..Character::default() };
+        c.take_damage(10, false);
+        assert_eq!(c.vitality(), Vitality::Dead);
+    }
+
+    #[test]
+    fn temp_hp_absorb_damage_first_and_do_not_stack() {
+        let mut c = Character { hp_current: 8, ..Character::default() };
+        c.gain_temp_hp(5);
+        c.gain_temp_hp(3);
+        assert_eq!(c.hp_temp, 5);
+        assert_eq!(c.take_damage(4, false), 4);
+        assert_eq!((c.hp_temp, c.hp_current), (1, 8));
+        assert_eq!(c.take_damage(6, false), 1);
+        assert_eq!((c.hp_temp, c.hp_current), (0, 3));
+        c.heal(20);
+        assert_eq!(c.hp_current, 10);
+    }
+
+    #[test]
+    fn hp_max_reduction_caps_current_hp() {
+        let mut c = Character::default();
+        c.set_hp_max_reduction(4);
+        assert_eq!((c.effective_hp_max(), c.hp_current), (6, 6));
+        c.heal(10);
+        assert_eq!(c.hp_current, 6);
+        c.set_hp(9);
+        assert_eq!(c.hp_current, 6);
+        c.set_hp_max_reduction(0);
+        assert_eq!((c.effective_hp_max(), c.hp_current), (10, 6));
+        c.set_hp_max_reduction(15);
+        assert_eq!((c.hp_max_reduction, c.effective_hp_max(), c.vitality()), (10, 0, Vitality::Dead));
+    }
+
+    #[test]
+    fn resolves_resistances_vulnerabilities_and_immunities() {
+        let c = Character {
+            resistances: vec![DamageType::Fire, DamageType::Cold],
+            vulnerabilities: vec![DamageType::Cold, DamageType::Radiant],
+            immunities: vec![DamageType::Poison],
+            ..Character::default()
+        };
+        let total = |amount, kind: &str| c.resolve_damage(amount, DamageType::from_name(kind)).total;
+        assert_eq!(total(13, "fire"), 6);
+        assert_eq!(total(13, "radiant"), 26);
+        assert_eq!(total(13, "cold"), 12);
+        assert_eq!(total(13, "poison"), 0);
+        assert_eq!(total(13, "slashing"), 13);
+        assert_eq!(total(13, ""), 13);
+        assert_eq!(c.resolve_damage(12, Some(DamageType::Fire)).to_string(), "12 fire ÷ 2 (resistant) = 6");
+        assert_eq!(c.resolve_damage(12, Some(DamageType::Poison)).to_string(), "12 poison (immune) = 0");
+        assert_eq!(DamageType::from_name(" Fire "), Some(DamageType::Fire));
+    }
+
+    #[test]
+    fn damage_records_have_no_dice_or_modifier() {
+        let c = Character { resistances: vec![DamageType::Fire], ..Character::default() };
+        let record = RollRecord::damage(Some(1), &c.resolve_damage(12, Some(DamageType::Fire)));
+        assert_eq!(record.kind, RecordKind::Damage);
+        assert_eq!((record.expression.as_str(), record.modifier, record.total), ("12 fire", 0, 6));
+        assert!(record.dice.is_empty());
+    }
+
+    #[test]
+    fn hit_dice_are_tracked_per_die_size() {
+        let mut c = Character::default();
+        c.set_class("Fighter 1 / Wizard 4");
+        assert_eq!(c.hit_dice(), [HitDice { size: 10, remaining: 1, total: 1 }, HitDice { size: 6, remaining: 4, total: 4 }]);
+        assert_eq!(c.spend_hit_dice(2, None), [10, 6]);
+        assert_eq!(c.spend_hit_dice(9, Some(6)), [6, 6, 6]);
+        assert!(c.spend_hit_dice(1, None).is_empty());
+        assert_eq!(c.hit_die_roll(6).to_string(), "1d6");
+        c.set_class("Fighter 1");
+        assert_eq!(c.hit_dice(), [HitDice { size: 10, remaining: 0, total: 1 }]);
+        c.level_up();
+        assert_eq!(c.hit_dice(), [HitDice { size: 10, remaining: 1, total: 2 }]);
+    }
+
+    #[test]
+    fn parses_hit_dice_to_spend() {
+        assert_eq!(parse_hit_dice("2"), Some(vec![(2, None)]));
+        assert_eq!(parse_hit_dice("1d10, d6"), Some(vec![(1, Some(10)), (1, Some(6))]));
+        assert_eq!(parse_hit_dice(""), Some(vec![]));
+        assert_eq!(parse_hit_dice("-1"), None);
+        assert_eq!(parse_hit_dice("2d"), None);
+    }
+
+    #[test]
+    fn rests_recharge_resources_by_their_rule() {
+        let mut c = Character::default();
+        c.set_class("Fighter 4 / Wizard 4");
+        c.set_resource("second wind", 1, Recharge::Short);
+        c.set_resource("arcane recovery", 1, Recharge::Long);
+        c.resources.iter_mut().for_each(|r| r.current = 0);
+        c.adjust_spell_slot(1, -2);
+        c.spend_hit_dice(8, None);
+        c.gain_temp_hp(2);
+        c.set_hp_max_reduction(2);
+        c.hp_current = 3;
+        c.exhaustion = 2;
+        let before = c.clone();
+
+        c.short_rest();
+        assert_eq!(c.resources.iter().map(|r| r.current).collect::<Vec<_>>(), [1, 0]);
+        assert_eq!(c.hp_current, 3);
+        assert_eq!(c.spell_slots, before.spell_slots);
+        assert_eq!(c.hit_dice_spent, before.hit_dice_spent);
+
+        c.long_rest();
+        assert_eq!(c.resources.iter().map(|r| r.current).collect::<Vec<_>>(), [1, 1]);
+        assert_eq!((c.hp_current, c.hp_temp, c.hp_max_reduction, c.exhaustion), (10, 0, 0, 1));
+        assert_eq!(c.spell_slots, c.spell_slots_max);
+        // Half the total hit dice come back, largest first.
+        assert_eq!(c.hit_dice(), [HitDice { size: 10, remaining: 4, total: 4 }, HitDice { size: 6, remaining: 0, total: 4 }]);
+    }
+
+    #[test]
+    fn long_rests_need_at_least_one_hp() {
+        let mut c = Character { hp_current: 0, death_failures: 1, ..Character::default() };
+        c.spend_hit_dice(1, None);
+        assert!(!c.can_long_rest());
+        c.long_rest();
+        assert_eq!((c.hp_current, c.death_failures, c.hit_dice()[0].remaining), (0, 1, 0));
+        c.hp_current = 1;
+        c.long_rest();
+        assert_eq!((c.hp_current, c.death_failures, c.hit_dice()[0].remaining), (10, 0, 1));
+    }
+
+    fn character(class_name: &str, level: i32) -> Character {
+        let mut c = Character { class_name: class_name.to_string(), level, ..Character::default() };
+        c.sync_spell_slots();
+        c
+    }
+
+    #[test]
+    fn spell_slots_follow_the_caster_tables() {
+        assert_eq!(spell_slots_for(0), [0; 9]);
+        assert_eq!(spell_slots_for(1), [2, 0, 0, 0, 0, 0, 0, 0, 0]);
+        assert_eq!(spell_slots_for(20), [4, 3, 3, 3, 3, 2, 2, 1, 1]);
+        assert_eq!(character("Wizard", 5).spell_slots_max, [4, 3, 2, 0, 0, 0, 0, 0, 0]);
+        assert_eq!(character("Paladin", 1).spell_slots_max, [0; 9]);
+        assert_eq!(character("Paladin", 5).spell_slots_max, [4, 2, 0, 0, 0, 0, 0, 0, 0]);
+        assert_eq!(character("Fighter (Eldritch Knight)", 3).spell_slots_max, [2, 0, 0, 0, 0, 0, 0, 0, 0]);
+        assert_eq!(character("Cleric (Life)", 3).spell_slots_max, [4, 2, 0, 0, 0, 0, 0, 0, 0]);
+        assert_eq!(character("Fighter", 5).spell_slots_max, [0; 9]);
+    }
+
+    #[test]
+    fn multiclass_casters_add_their_levels() {
+        let classes = |s: &str| parse_class_levels(s).unwrap();
+        assert_eq!(spellcaster_level(&classes("Paladin 2 / Sorcerer 3")), 4);
+        assert_eq!(spellcaster_level(&classes("Ranger 3 / Rogue (Arcane Trickster) 3 / Wizard 1")), 3);
+        assert_eq!(spellcaster_level(&classes("Warlock 3 / Fighter 2")), 0);
+        let mut c = Character::default();
+        c.set_class("Paladin 2 / Sorcerer 3");
+        assert_eq!(c.level, 5);
+        assert_eq!(c.spell_slots_max, [4, 3, 0, 0, 0, 0, 0, 0, 0]);
+        assert_eq!(c.spell_slots, c.spell_slots_max);
+    }
+
+    #[test]
+    fn caster_kind_ignores_subclasses() {
+        assert_eq!(caster_kind("Cleric (Life)"), Some(Caster::Full));
+        assert_eq!(caster_kind("Wizard (Evoker)"), Some(Caster::Full));
+        assert_eq!(caster_kind("Ranger"), Some(Caster::Half));
+        assert_eq!(caster_kind("Rogue (Arcane Trickster)"), Some(Caster::Third));
+        assert_eq!(caster_kind("Warlock"), None);
+        assert_eq!(caster_kind("Barbarian"), None);
+    }
+
+    #[test]
+    fn pact_magic_follows_the_warlock_level() {
+        assert_eq!(pact_slots_for(0), (0, 0));
+        assert_eq!(pact_slots_for(1), (1, 1));
+        assert_eq!(pact_slots_for(2), (2, 1));
+        assert_eq!(pact_slots_for(5), (2, 3));
+        assert_eq!(pact_slots_for(11), (3, 5));
+        assert_eq!(pact_slots_for(20), (4, 5));
+        let mut c = Character::default();
+        c.set_class("Warlock 2 / Wizard 3");
+        assert_eq!((c.pact_slots, c.pact_slots_max, c.pact_slot_level), (2, 2, 1));
+        assert_eq!(c.spell_slots_max, [4, 2, 0, 0, 0, 0, 0, 0, 0]);
+        c.pact_slots = 0;
+        c.spell_slots[0] = 0;
+        c.short_rest();
+        assert_eq!((c.pact_slots, c.spell_slots[0]), (2, 0));
+    }
+
+    #[test]
+    fn only_preparing_classes_must_prepare_their_spells() {
+        let spell = |s: &str| Spell::parse(s).unwrap();
+        let mut c = Character::default();
+        c.set_class("Cleric 3 / Sorcerer 2");
+        assert!(c.must_prepare(&spell("Bless | 1 | Cleric")));
+        assert!(!c.must_prepare(&spell("Shield | 1 | Sorcerer (Draconic)")));
+        assert!(!c.must_prepare(&spell("Light | 0 | Cleric")));
+        // Without a class it could be either, so it's castable unprepared.
+        assert!(!c.must_prepare(&spell("Sleep | 1")));
+        c.set_class("Cleric 3 / Wizard 2");
+        assert!(c.must_prepare(&spell("Sleep | 1")));
+        c.set_class("Fighter 3");
+        assert!(!c.must_prepare(&spell("Sleep | 1")));
+    }
+
+    #[test]
+    fn conditions_affect_rolls_by_kind() {
+        let mut c = Character { conditions: vec![Condition::Poisoned, Condition::Prone, Condition::Paralyzed], ..Character::default() };
+        assert_eq!(c.roll_effects(RollKind::Check).disadvantage, ["poisoned"]);
+        assert_eq!(c.roll_effects(RollKind::Attack).disadvantage, ["poisoned", "prone"]);
+        let save = c.roll_effects(RollKind::Save(Some(1)));
+        assert!(save.disadvantage.is_empty());
+        assert_eq!(save.auto_fail, ["paralyzed"]);
+        assert_eq!(c.roll_effects(RollKind::Save(Some(4))), RollEffects::default());
+
+        c.conditions = vec![Condition::Invisible];
+        c.exhaustion = 1;
+        assert_eq!(c.roll_effects(RollKind::Check).disadvantage, ["exhaustion"]);
+        assert_eq!(c.roll_effects(RollKind::Attack), RollEffects { advantage: vec!["invisible"], ..RollEffects::default() });
+        c.exhaustion = 3;
+        assert_eq!(c.roll_effects(RollKind::Save(None)).disadvantage, ["exhaustion"]);
+    }
+
+    #[test]
+    fn rolls_are_attacks_or_checks_only_when_tagged() {
+        assert_eq!(RollKind::split_tag("1d20+7 attack"), ("1d20+7", Some(RollKind::Attack)));
+        assert_eq!(RollKind::split_tag(" 1d20+@wis check "), ("1d20+@wis", Some(RollKind::Check)));
+        assert_eq!(RollKind::split_tag("1d20+5"), ("1d20+5", None));
+        let mut c = Character::default();
+        c.set_macro("longsword-hit", "1d20+5", Some(RollKind::Attack));
+        c.set_macro("longsword", "1d20+5 ; 1d8+3", None);
+        c.set_macro("luck", "1d20", None);
+        assert_eq!(c.find_macro("longsword-hit").unwrap().roll_kind(), Some(RollKind::Attack));
+        assert_eq!(c.find_macro("longsword").unwrap().roll_kind(), Some(RollKind::Attack));
+        assert_eq!(c.find_macro("luck").unwrap().roll_kind(), None);
+    }
+
+    #[test]
+    fn item_values_parse_with_or_without_a_coin() {
+        assert_eq!(parse_gp("50"), Some(50.0));
+        assert_eq!(parse_gp("5 sp"), Some(0.5));
+        assert_eq!(parse_gp("2PP"), Some(20.0));
+        assert_eq!(parse_gp("5€"), None);
+        assert_eq!(parse_gp("€"), None);
+        assert_eq!(parse_gp("-1 gp"), None);
+    }
+
+    #[test]
+    fn spending_coins_makes_change() {
+        let mut purse = Purse { gp: 1, ..Purse::default() };
+        purse.spend(3, Coin::Sp).unwrap();
+        assert_eq!(purse, Purse { sp: 7, ..Purse::default() });
+
+        let mut purse = Purse { pp: 1, ..Purse::default() };
+        purse.spend(5, Coin::Cp).unwrap();
+        assert_eq!(purse, Purse { gp: 9, sp: 9, cp: 5, ..Purse::default() });
+
+        let mut purse = Purse { gp: 2, cp: 50, ..Purse::default() };
+        purse.spend(2, Coin::Gp).unwrap();
+        assert_eq!(purse, Purse { cp: 50, ..Purse::default() });
+
+        let mut purse = Purse { sp: 8, cp: 30, ..Purse::default() };
+        purse.spend(1, Coin::Gp).unwrap();
+        assert_eq!(purse, Purse { cp: 10, ..Purse::default() });
+        assert!(purse.spend(1, Coin::Sp).is_ok());
+        assert!(purse.spend(1, Coin::Cp).is_err());
+    }
+
+    #[test]
+    fn huge_coin_amounts_are_rejected() {
+        let mut purse = Purse { pp: 1, ..Purse::default() };
+        assert!(purse.spend(i64::MAX / 10, Coin::Pp).is_err());
+        assert!(purse.add(i64::MAX / 10, Coin::Pp).is_err());
+        assert!(purse.add(i64::MAX, Coin::Cp).is_err());
+        assert_eq!(purse, Purse { pp: 1, ..Purse::default() });
+        assert_eq!(Purse { pp: i64::MAX, ..Purse::default() }.total_cp(), i64::MAX);
+    }
+
+    #[test]
+    fn converts_and_parses_coins() {
+        let mut purse = Purse { sp: 35, ..Purse::default() };
+        assert_eq!(purse.convert(35, Coin::Sp, Coin::Gp), Ok(3));
+        assert_eq!(purse, Purse { sp: 5, gp: 3, ..Purse::default() });
+        assert!(purse.convert(5, Coin::Sp, Coin::Pp).is_err());
+        assert!(purse.convert(6, Coin::Sp, Coin::Cp).is_err());
+        assert_eq!(parse_coins("3 gp 5sp"), Ok(vec![(3, Coin::Gp), (5, Coin::Sp)]));
+        assert!(parse_coins("3 bananas").is_err());
+        assert!(parse_coins("99999999999999999999 gp").is_err());
+    }
+
+    #[test]
+    fn encumbrance_follows_strength_thresholds() {
+        let mut c = Character { strength: 10, speed: 30, ..Character::default() };
+        assert_eq!((c.carrying_capacity(), c.push_drag_lift()), (150.0, 300.0));
+        let at = |load: f64, variant: bool| c.encumbrance(load, variant);
+        assert_eq!(at(50.0, true), Encumbrance::Unencumbered);
+        assert_eq!(at(51.0, true), Encumbrance::Encumbered);
+        assert_eq!(at(100.0, true), Encumbrance::Encumbered);
+        assert_eq!(at(101.0, true), Encumbrance::HeavilyEncumbered);
+        assert_eq!(at(150.0, true), Encumbrance::HeavilyEncumbered);
+        assert_eq!(at(150.0, false), Encumbrance::Unencumbered);
+        assert_eq!(at(151.0, false), Encumbrance::OverCapacity);
+
+        assert_eq!(c.effective_speed(Encumbrance::Encumbered), 20);
+        assert_eq!(c.effective_speed(Encumbrance::HeavilyEncumbered), 10);
+        assert_eq!(c.effective_speed(Encumbrance::OverCapacity), 5);
+        c.exhaustion = 2;
+        assert_eq!(c.effective_speed(Encumbrance::Encumbered), 10);
+        // Coins count towards the load at 50 to the pound.
+        assert_eq!(Purse { gp: 40, sp: 10, ..Purse::default() }.weight(), 1.0);
+        assert_eq!(Item { weight: 0.5, quantity: 3, ..Item::named("Potion") }.total_weight(), 1.5);
+    }
+}
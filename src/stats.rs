@@ -0,0 +1,318 @@
+use std::collections::HashMap;
+
+use thiserror::Error;
+
+use crate::dice::{BinOp, Compare, DiceError, DiceTerm, Expr, Keep, MAX_CHAIN};
+
+/// Probabilities below this are trimmed from the tails of a distribution.
+const EPSILON: f64 = 1e-12;
+/// Upper bound on the number of distinct totals tracked.
+const MAX_OUTCOMES: i64 = 1_000_000;
+/// Upper bound on the work spent on a keep/drop term, in DP transitions.
+const MAX_KEEP_WORK: usize = 50_000_000;
+/// Upper bound on the pairs of outcomes combined by sums and products.
+const MAX_PAIR_WORK: usize = 10_000_000;
+
+#[derive(Debug, Clone, PartialEq, Eq, Error)]
+pub enum StatsError {
+    #[error(transparent)]
+    Dice(#[from] DiceError),
+    #[error("too many possible outcomes to analyze exactly")]
+    TooComplex,
+    #[error("keep/drop can't be analyzed together with '!' explosions")]
+    ExplodingKeep,
+}
+
+/// Exact probability distribution of a dice expression's total.
+///
+/// Explosions and rerolls follow the same [`MAX_CHAIN`] cap as real rolls,
+/// so the numbers match what [`crate::dice::DiceRoller`] produces.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Distribution {
+    /// Value of `probs[0]`.
+    offset: i32,
+    probs: Vec<f64>,
+}
+
+impl Distribution {
+    pub fn constant(value: i32) -> Self {
+        Self { offset: value, probs: vec![1.0] }
+    }
+
+    fn uniform(sides: i32) -> Self {
+        Self { offset: 1, probs: vec![1.0 / sides as f64; sides as usize] }
+    }
+
+    /// Computes the distribution of `expr`.
+    pub fn of(expr: &Expr) -> Result<Self, StatsError> {
+        match expr {
+            Expr::Num(n) => Ok(Self::constant(*n)),
+            Expr::Dice(term) => Self::of_term(term),
+            Expr::Neg(inner) => Self::of(inner)?.negate(),
+            Expr::Binary(op, lhs, rhs) => {
+                let l = Self::of(lhs)?;
+                let r = Self::of(rhs)?;
+                match op {
+                    BinOp::Add => l.convolve(&r),
+                    BinOp::Sub => l.convolve(&r.negate()?),
+                    BinOp::Mul => l.product(&r),
+                }
+            }
+        }
+    }
+
+    /// `(value, probability)` pairs in ascending order of value.
+    pub fn iter(&self) -> impl Iterator<Item = (i32, f64)> + '_ {
+        self.probs.iter().enumerate().map(|(i, p)| (self.offset + i as i32, *p))
+    }
+
+    pub fn min(&self) -> i32 {
+        self.offset
+    }
+
+    pub fn max(&self) -> i32 {
+        self.offset + self.probs.len() as i32 - 1
+    }
+
+    pub fn mean(&self) -> f64 {
+        self.iter().map(|(v, p)| v as f64 * p).sum()
+    }
+
+    pub fn stddev(&self) -> f64 {
+        let mean = self.mean();
+        self.iter().map(|(v, p)| (v as f64 - mean).powi(2) * p).sum::<f64>().sqrt()
+    }
+
+    /// Chance that the total meets or beats `target`, e.g. a DC or AC.
+    pub fn prob_at_least(&self, target: i32) -> f64 {
+        self.iter().filter(|(v, _)| *v >= target).fold(0.0, |acc, (_, p)| acc + p)
+    }
+
+    fn of_term(term: &DiceTerm) -> Result<Self, StatsError> {
+        let die = Self::die(term)?;
+        let count = term.count as usize;
+        let Some(keep) = term.keep else {
+            let pairs: usize = (1..count).map(|k| (k * (die.probs.len() - 1) + 1) * die.probs.len()).sum();
+            if pairs > MAX_PAIR_WORK { return Err(StatsError::TooComplex); }
+            let mut total = die.clone();
+            for _ in 1..count {
+                total = total.convolve(&die)?;
+            }
+            return Ok(total);
+        };
+        if term.explode.is_some_and(|e| !e.compound) {
+            return Err(StatsError::ExplodingKeep);
+        }
+        let (kept, highest) = match keep {
+            Keep::Highest(n) => (n as usize, true),
+            Keep::Lowest(n) => (n as usize, false),
+            Keep::DropHighest(n) => (count.saturating_sub(n as usize), false),
+            Keep::DropLowest(n) => (count.saturating_sub(n as usize), true),
+        };
+        die.keep(count, kept, highest)
+    }
+
+    /// Distribution of one die of `term`, after rerolls, minimum and explosions.
+    fn die(term: &DiceTerm) -> Result<Self, StatsError> {
+        let uniform = Self::uniform(term.sides);
+        let mut face = uniform.clone();
+        if let Some(reroll) = term.reroll {
+            let rounds = if reroll.once { 1 } else { MAX_CHAIN };
+            for _ in 0..rounds {
+                let matched: f64 = face.iter().filter(|(v, _)| reroll.on.matches(*v)).map(|(_, p)| p).sum();
+                for (i, p) in face.probs.iter_mut().enumerate() {
+                    if reroll.on.matches(i as i32 + 1) { *p = 0.0; }
+                    *p += matched / term.sides as f64;
+                }
+            }
+        }
+        if let Some(min) = term.min {
+            let mut raised = HashMap::new();
+            for (v, p) in face.iter() {
+                *raised.entry(v.max(min)).or_insert(0.0) += p;
+            }
+            face = Self::from_map(&raised)?;
+        }
+        let Some(explode) = term.explode else { return Ok(face.trim()) };
+        // Compounded faces are plain rolls; `!` adds whole dice with reroll and minimum.
+        let extra = if explode.compound { uniform } else { face.clone() };
+        let mut tail = extra.clone();
+        for _ in 1..MAX_CHAIN {
+            tail = extra.explode_into(&tail, explode.on)?;
+        }
+        face.explode_into(&tail, explode.on)
+    }
+
+    /// Adds `tail` to every outcome of `self` that triggers an explosion.
+    fn explode_into(&self, tail: &Self, on: Compare) -> Result<Self, StatsError> {
+        let mut out = HashMap::new();
+        for (v, p) in self.iter() {
+            if !on.matches(v) {
+                *out.entry(v).or_insert(0.0) += p;
+                continue;
+            }
+            for (t, q) in tail.iter() {
+                *out.entry(v + t).or_insert(0.0) += p * q;
+            }
+        }
+        Self::from_map(&out)
+    }
+
+    /// Sum of the `kept` highest (or lowest) of `count` independent copies of `self`.
+    ///
+    /// Walks the faces from the kept end; the first `kept` dice assigned are the ones kept.
+    fn keep(&self, count: usize, kept: usize, highest: bool) -> Result<Self, StatsError> {
+        let mut faces: Vec<(i32, f64)> = self.iter().filter(|(_, p)| *p > 0.0).collect();
+        if highest { faces.reverse(); }
+        let work = faces.len() * (count + 1) * (count + 1) * (kept * self.probs.len() + 1);
+        if work > MAX_KEEP_WORK { return Err(StatsError::TooComplex); }
+
+        let mut binom = vec![vec![1.0f64; count + 1]; count + 1];
+        for n in 1..=count {
+            for k in 1..n {
+                binom[n][k] = binom[n - 1][k - 1] + binom[n - 1][k];
+            }
+        }
+        // states[m] maps the kept sum to its probability after assigning m dice.
+        let mut states: Vec<HashMap<i32, f64>> = vec![HashMap::new(); count + 1];
+        states[0].insert(0, 1.0);
+        for (value, p) in faces {
+            let mut next: Vec<HashMap<i32, f64>> = vec![HashMap::new(); count + 1];
+            for (m, sums) in states.iter().enumerate() {
+                for (&sum, &q) in sums {
+                    let mut pj = 1.0;
+                    for j in 0..=count - m {
+                        let take = kept.saturating_sub(m).min(j) as i32;
+                        *next[m + j].entry(sum + take * value).or_insert(0.0) += q * binom[count - m][j] * pj;
+                        pj *= p;
+                    }
+                }
+            }
+            states = next;
+        }
+        Self::from_map(&states[count])
+    }
+
+    fn convolve(&self, other: &Self) -> Result<Self, StatsError> {
+        let len = self.probs.len() + other.probs.len() - 1;
+        if len as i64 > MAX_OUTCOMES { return Err(StatsError::TooComplex); }
+        if self.probs.len() * other.probs.len() > MAX_PAIR_WORK { return Err(StatsError::TooComplex); }
+        // Both ends of the sum must fit, so `max()` can't overflow later.
+        let offset = self.offset.checked_add(other.offset).ok_or(StatsError::TooComplex)?;
+        offset.checked_add(len as i32 - 1).ok_or(StatsError::TooComplex)?;
+        let mut probs = vec![0.0; len];
+        for (i, p) in self.probs.iter().enumerate() {
+            for (j, q) in other.probs.iter().enumerate() {
+                probs[i + j] += p * q;
+            }
+        }
+        Ok(Self { offset, probs }.trim())
+    }
+
+    fn product(&self, other: &Self) -> Result<Self, StatsError> {
+        if self.probs.len() * other.probs.len() > MAX_PAIR_WORK { return Err(StatsError::TooComplex); }
+        // The extremes are products of the ends, so the range is known before any work.
+        let ends = [self.min(), self.max()].map(|a| [other.min(), other.max()].map(|b| i64::from(a) * i64::from(b)));
+        let (lo, hi) = ends.iter().flatten().fold((i64::MAX, i64::MIN), |(lo, hi), &v| (lo.min(v), hi.max(v)));
+        if hi - lo + 1 > MAX_OUTCOMES { return Err(StatsError::TooComplex); }
+        let mut out = HashMap::new();
+        for (a, p) in self.iter() {
+            for (b, q) in other.iter() {
+                let v = a.checked_mul(b).ok_or(StatsError::TooComplex)?;
+                *out.entry(v).or_insert(0.0) += p * q;
+            }
+        }
+        Self::from_map(&out)
+    }
+
+    fn negate(mut self) -> Result<Self, StatsError> {
+        // The minimum becomes the new maximum, so it has to be negatable too.
+        self.min().checked_neg().ok_or(StatsError::TooComplex)?;
+        self.offset = -self.max();
+        self.probs.reverse();
+        Ok(self)
+    }
+
+    fn from_map(map: &HashMap<i32, f64>) -> Result<Self, StatsError> {
+        let (Some(&lo), Some(&hi)) = (map.keys().min(), map.keys().max()) else {
+            return Ok(Self::constant(0));
+        };
+        if hi as i64 - lo as i64 + 1 > MAX_OUTCOMES { return Err(StatsError::TooComplex); }
+        let mut probs = vec![0.0; (hi - lo + 1) as usize];
+        for (v, p) in map {
+            probs[(v - lo) as usize] += p;
+        }
+        Ok(Self { offset: lo, probs }.trim())
+    }
+
+    /// Drops negligible outcomes from both ends.
+    fn trim(mut self) -> Self {
+        let start = self.probs.iter().position(|p| *p > EPSILON).unwrap_or(0);
+        let end = self.probs.iter().rposition(|p| *p > EPSILON).map_or(self.probs.len(), |i| i + 1);
+        if start > 0 || end < self.probs.len() {
+            self.probs = self.probs[start..end.max(start + 1)].to_vec();
+            self.offset += start as i32;
+        }
+        self
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::dice::{parse, DiceRoller};
+
+    fn odds(input: &str) -> Distribution {
+        Distribution::of(&parse(input).unwrap()).unwrap()
+    }
+
+    fn assert_close(actual: f64, expected: f64) {
+        assert!((actual - expected).abs() < 1e-9, "{} != {}", actual, expected);
+    }
+
+    #[test]
+    fn probabilities_sum_to_one() {
+        for input in [
+            "1d20", "3d6+2", "4d6kh3", "4d6dl1", "2d20kl1", "1d6!", "1d6!!", "2d6!>5", "1d8r1", "2d6ro<3",
+            "1d20min10", "(1d4+1)*2", "-1d4", "1d12-1d4",
+        ] {
+            assert_close(odds(input).iter().map(|(_, p)| p).sum(), 1.0);
+        }
+    }
+
+    #[test]
+    fn matches_known_odds() {
+        let d6 = odds("1d6");
+        assert_eq!((d6.min(), d6.max()), (1, 6));
+        assert_close(d6.mean(), 3.5);
+        assert_close(odds("2d6").iter().find(|(v, _)| *v == 7).unwrap().1, 1.0 / 6.0);
+        assert_close(odds("2d20kh1").prob_at_least(20), 39.0 / 400.0);
+        assert_close(odds("2d20kl1").prob_at_least(20), 1.0 / 400.0);
+        assert_close(odds("1d20min10").iter().next().unwrap().1, 0.5);
+        assert_close(odds("1d6ro1").prob_at_least(2), 1.0 - 1.0 / 36.0);
+        assert_close(odds("-1d4").mean(), -2.5);
+    }
+
+    #[test]
+    fn seeded_rolls_follow_the_distribution() {
+        let exact = odds("4d6kh3").mean();
+        let mut roller = DiceRoller::seeded(5);
+        let n = 20_000;
+        let mean = (0..n).map(|_| roller.roll("4d6kh3").unwrap().total as f64).sum::<f64>() / n as f64;
+        assert!((mean - exact).abs() < 0.05, "{} vs {}", mean, exact);
+    }
+
+    #[test]
+    fn rejects_what_it_cannot_analyze() {
+        assert_eq!(Distribution::of(&parse("4d6!kh3").unwrap()), Err(StatsError::ExplodingKeep));
+    }
+
+    #[test]
+    fn rejects_totals_and_work_out_of_range() {
+        for input in ["2000000000+2000000000", "-2000000000-2000000000", "-(-65536*32768)", "1d1000*1d1000*1d20", "100d1000*100d1000"] {
+            assert_eq!(Distribution::of(&parse(input).unwrap()), Err(StatsError::TooComplex), "{}", input);
+        }
+        assert_eq!(odds("2000000000+100000000").min(), 2_100_000_000);
+        assert_eq!(odds("3d100").max(), 300);
+    }
+}